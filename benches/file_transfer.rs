@@ -0,0 +1,78 @@
+//! Large file transfer throughput with and without sendfile(2).
+//! Run with "cargo bench", the file size in MiB can be set by the FILE_SIZE_MIB environment variable.
+use std::io::{Read, Write};
+use std::{env, fs, io, net, process, thread, time};
+
+const DEFAULT_FILE_SIZE_MIB: usize = 512;
+const DOWNLOADS_NUMBER: usize = 5;
+
+fn main() {
+    let file_size_mib = env::var("FILE_SIZE_MIB")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_FILE_SIZE_MIB);
+    let root_folder_path = env::temp_dir().join("simple_web_server_file_transfer_bench");
+    fs::create_dir_all(&root_folder_path).unwrap();
+    let chunk: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let mut file = fs::File::create(root_folder_path.join("large.bin")).unwrap();
+    for _ in 0..file_size_mib {
+        file.write_all(&chunk).unwrap();
+    }
+    drop(file);
+
+    println!("Downloading {file_size_mib} MiB file {DOWNLOADS_NUMBER} times");
+    for (name, extra_args) in [
+        ("buffered copy", &["--no-sendfile"][..]),
+        ("sendfile", &[][..]),
+    ] {
+        let throughput = measure(&root_folder_path, extra_args, file_size_mib);
+        println!("{name:>14}: {throughput:8.1} MiB/s");
+    }
+
+    let _ = fs::remove_dir_all(&root_folder_path);
+}
+
+/// Starts the server and returns the average download throughput in MiB/s
+fn measure(root_folder_path: &std::path::Path, extra_args: &[&str], file_size_mib: usize) -> f64 {
+    // Free port chosen by the system
+    let addr = net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut server = process::Command::new(env!("CARGO_BIN_EXE_simple_web_server"))
+        .args(["--addr", &addr.to_string()])
+        .args(["--root_folder", root_folder_path.to_str().unwrap()])
+        .args(extra_args)
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .spawn()
+        .unwrap();
+    // Wait for the server to start
+    while net::TcpStream::connect(addr).is_err() {
+        thread::sleep(time::Duration::from_millis(10));
+    }
+
+    let mut buffer = vec![0; 1024 * 1024];
+    let start = time::Instant::now();
+    for _ in 0..DOWNLOADS_NUMBER {
+        let mut stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /large.bin HTTP/1.1\r\nHost: bench\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut received = 0;
+        loop {
+            match stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => received += read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => panic!("{error}"),
+            }
+        }
+        assert!(received > file_size_mib * 1024 * 1024);
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let _ = server.kill();
+    let _ = server.wait();
+    (file_size_mib * DOWNLOADS_NUMBER) as f64 / elapsed
+}
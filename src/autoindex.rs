@@ -0,0 +1,349 @@
+/// Automatic folder listing (autoindex)
+use crate::{date_time, request};
+use std::{fmt::Write, fs, io, path, time};
+
+/// Listing entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    is_folder: bool,
+    size: u64,
+    modified: time::SystemTime,
+}
+
+/// Column by which the listing is sorted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortColumn {
+    Name,
+    Size,
+    Modified,
+}
+
+impl SortColumn {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Size => "size",
+            Self::Modified => "modified",
+        }
+    }
+}
+
+/// Listing of the folder
+pub struct Listing {
+    /// Percent-encoded path of the folder from the request, ends with '/'
+    request_path: String,
+    entries: Vec<Entry>,
+    sort_column: SortColumn,
+    descending: bool,
+}
+
+impl Listing {
+    /// Reads the folder entries.
+    /// The listing is sorted according to the "sort" (name, size or modified) and "order" (asc or desc) query parameters,
+    /// folders are always listed before files.
+    pub fn read(
+        canonical_root_folder_path: &path::Path,
+        folder_path: &path::Path,
+        request: &request::Request,
+        show_hidden: bool,
+    ) -> Result<Self, io::Error> {
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(folder_path)? {
+            let dir_entry = dir_entry?;
+            let Ok(name) = dir_entry.file_name().into_string() else {
+                // Names that are not UTF-8 can not be shown
+                continue;
+            };
+            if !show_hidden && name.starts_with('.') {
+                continue;
+            }
+            // Symbolic links are followed only inside the root folder, like the requested files are.
+            // Broken links and the links leading outside the root folder are skipped.
+            let Ok(file_type) = dir_entry.file_type() else {
+                continue;
+            };
+            let metadata = if file_type.is_symlink() {
+                match dir_entry.path().canonicalize() {
+                    Ok(target_path) if target_path.starts_with(canonical_root_folder_path) => {
+                        fs::metadata(target_path)
+                    }
+                    _ => continue,
+                }
+            } else {
+                dir_entry.metadata()
+            };
+            let Ok(metadata) = metadata else {
+                continue;
+            };
+            entries.push(Entry {
+                name,
+                is_folder: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified: metadata.modified().unwrap_or(time::UNIX_EPOCH),
+            });
+        }
+
+        let sort_column = match request.query.get("sort") {
+            Some("size") => SortColumn::Size,
+            Some("modified") => SortColumn::Modified,
+            _ => SortColumn::Name,
+        };
+        let descending = request.query.get("order") == Some("desc");
+        entries.sort_by(|a, b| {
+            let ordering = match sort_column {
+                SortColumn::Name => a.name.cmp(&b.name),
+                SortColumn::Size => a.size.cmp(&b.size).then_with(|| a.name.cmp(&b.name)),
+                SortColumn::Modified => a
+                    .modified
+                    .cmp(&b.modified)
+                    .then_with(|| a.name.cmp(&b.name)),
+            };
+            let ordering = if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            // Folders first
+            b.is_folder.cmp(&a.is_folder).then(ordering)
+        });
+
+        Ok(Self {
+            request_path: request.path.clone(),
+            entries,
+            sort_column,
+            descending,
+        })
+    }
+
+    /// Renders the listing as an HTML page
+    pub fn to_html(&self) -> String {
+        let title = html_escape(&urlencoding::decode(&self.request_path).unwrap_or_default());
+        let mut html = String::new();
+        let _ = write!(
+            &mut html,
+            "<!DOCTYPE html>\n\
+            <html lang=\"en\">\n\
+            <head>\n\
+            <meta charset=\"UTF-8\">\n\
+            <title>Index of {title}</title>\n\
+            <style>\n\
+            body {{ font-family: Arial, sans-serif; margin: 2em; }}\n\
+            th, td {{ padding: 0.2em 1em; text-align: left; }}\n\
+            td.size {{ text-align: right; }}\n\
+            </style>\n\
+            </head>\n\
+            <body>\n\
+            <h1>Index of {title}</h1>\n\
+            <table>\n\
+            <tr><th>{}</th><th>{}</th><th>{}</th></tr>\n",
+            self.sort_link(SortColumn::Name, "Name"),
+            self.sort_link(SortColumn::Size, "Size"),
+            self.sort_link(SortColumn::Modified, "Modified"),
+        );
+        if self.request_path != "/" {
+            html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+        }
+        for entry in &self.entries {
+            let suffix = if entry.is_folder { "/" } else { "" };
+            let size = if entry.is_folder {
+                "-".to_string()
+            } else {
+                entry.size.to_string()
+            };
+            let _ = writeln!(
+                &mut html,
+                "<tr><td><a href=\"{}{suffix}\">{}{suffix}</a></td><td class=\"size\">{size}</td><td>{}</td></tr>",
+                urlencoding::encode(&entry.name),
+                html_escape(&entry.name),
+                date_time::DateTime::from_system_time(entry.modified),
+            );
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+
+    /// Renders the listing as a JSON array
+    pub fn to_json(&self) -> String {
+        let entries: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{{\"name\":\"{}\",\"type\":\"{}\",\"size\":{},\"modified\":\"{}\"}}",
+                    json_escape(&entry.name),
+                    if entry.is_folder { "directory" } else { "file" },
+                    entry.size,
+                    date_time::DateTime::from_system_time(entry.modified),
+                )
+            })
+            .collect();
+        format!("[{}]", entries.join(","))
+    }
+
+    /// Forms the column header link, the second click on the current column reverses the order
+    fn sort_link(&self, column: SortColumn, title: &str) -> String {
+        let order = if column == self.sort_column && !self.descending {
+            "desc"
+        } else {
+            "asc"
+        };
+        let arrow = match (column == self.sort_column, self.descending) {
+            (false, _) => "",
+            (true, false) => " &#9650;",
+            (true, true) => " &#9660;",
+        };
+        format!(
+            "<a href=\"?sort={}&amp;order={order}\">{title}</a>{arrow}",
+            column.as_str()
+        )
+    }
+}
+
+/// Checks if the client prefers the JSON listing ("Accept: application/json") to the HTML one
+pub fn is_json_requested(request: &request::Request) -> bool {
+    let quality_of = |media_type: &str| {
+        request
+            .headers
+            .get_quality_list("Accept")
+            .filter(|(media_range, _)| media_range.eq_ignore_ascii_case(media_type))
+            .map(|(_, quality)| quality)
+            .fold(0.0, f32::max)
+    };
+    let json_quality = quality_of("application/json");
+    json_quality > 0.0 && json_quality > quality_of("text/html")
+}
+
+fn html_escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for ch in string.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn json_escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for ch in string.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => {
+                let _ = write!(&mut escaped, "\\u{:04x}", ch as u32);
+            }
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(query: &str) -> Listing {
+        let temp_dir = std::env::temp_dir().join(format!(
+            "simple_web_server_autoindex_{}",
+            query.replace(['=', '&'], "_")
+        ));
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("folder")).unwrap();
+        fs::write(temp_dir.join("b.txt"), "12345").unwrap();
+        fs::write(temp_dir.join("a <&>.txt"), "123").unwrap();
+        fs::write(temp_dir.join(".hidden"), "").unwrap();
+        let head = format!("GET /files/?{query} HTTP/1.0\r\n\r\n");
+        let request = request::parse(&head).unwrap();
+        let listing = Listing::read(&temp_dir, &temp_dir, &request, false);
+        let _ = fs::remove_dir_all(&temp_dir);
+        listing.unwrap()
+    }
+
+    fn names(listing: &Listing) -> Vec<&str> {
+        listing
+            .entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn sorted_listing_without_hidden_files() {
+        assert_eq!(names(&listing("")), ["folder", "a <&>.txt", "b.txt"]);
+        assert_eq!(
+            names(&listing("sort=size&order=desc")),
+            ["folder", "b.txt", "a <&>.txt"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symbolic_links_outside_root() {
+        use std::os::unix::fs::symlink;
+        let temp_dir = std::env::temp_dir().join("simple_web_server_autoindex_links");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let root_folder_path = temp_dir.join("root");
+        fs::create_dir_all(root_folder_path.join("folder")).unwrap();
+        fs::write(temp_dir.join("secret.txt"), "12345").unwrap();
+        symlink(
+            root_folder_path.join("folder"),
+            root_folder_path.join("inside"),
+        )
+        .unwrap();
+        symlink(
+            temp_dir.join("secret.txt"),
+            root_folder_path.join("secret.txt"),
+        )
+        .unwrap();
+        symlink(&temp_dir, root_folder_path.join("parent")).unwrap();
+        symlink(temp_dir.join("missing"), root_folder_path.join("broken")).unwrap();
+
+        let root_folder_path = root_folder_path.canonicalize().unwrap();
+        let request = request::parse("GET / HTTP/1.0\r\n\r\n").unwrap();
+        let listing = Listing::read(&root_folder_path, &root_folder_path, &request, true);
+        let _ = fs::remove_dir_all(&temp_dir);
+        let listing = listing.unwrap();
+        assert_eq!(names(&listing), ["folder", "inside"]);
+        assert!(listing.entries[1].is_folder);
+    }
+
+    #[test]
+    fn html_listing() {
+        let html = listing("").to_html();
+        assert!(html.contains("<title>Index of /files/</title>"));
+        assert!(html.contains("<a href=\"../\">../</a>"));
+        assert!(html.contains("<a href=\"folder/\">folder/</a>"));
+        assert!(html.contains("<a href=\"a%20%3C%26%3E.txt\">a &lt;&amp;&gt;.txt</a>"));
+        assert!(html.contains("<td class=\"size\">5</td>"));
+        assert!(!html.contains(".hidden"));
+    }
+
+    #[test]
+    fn json_listing() {
+        let json = listing("").to_json();
+        assert!(json.starts_with("[{\"name\":\"folder\",\"type\":\"directory\",\"size\":0,"));
+        assert!(json.contains("{\"name\":\"b.txt\",\"type\":\"file\",\"size\":5,"));
+    }
+
+    #[test]
+    fn json_requested() {
+        let is_json_requested_by = |accept: &str| {
+            let head = format!("GET / HTTP/1.0\r\nAccept: {accept}\r\n\r\n");
+            is_json_requested(&request::parse(&head).unwrap())
+        };
+        assert!(is_json_requested_by("application/json"));
+        assert!(is_json_requested_by("text/html;q=0.5, application/json"));
+        assert!(!is_json_requested_by("text/html, application/json;q=0.9"));
+        assert!(!is_json_requested_by("text/html,*/*;q=0.8"));
+        assert!(!is_json_requested_by("application/json;q=0"));
+    }
+}
@@ -0,0 +1,212 @@
+/// Cache-Control and Expires rules for the served files
+use crate::config;
+use std::path;
+
+/// Rule assigning the Cache-Control value to the files matching the pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    /// Glob pattern split into segments by '/'
+    pattern_segments: Vec<String>,
+    /// Pattern without '/' is matched against the file name only
+    match_file_name: bool,
+    cache_control: String,
+    /// max-age directive of the Cache-Control value, the Expires header is computed from it
+    max_age: Option<u64>,
+}
+
+/// Cache-Control rules, the first matching rule is applied
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheRules {
+    rules: Vec<Rule>,
+}
+
+impl CacheRules {
+    /// Creates the rules from the configuration.
+    /// Patterns without '/' ("*.html") are matched against the file name,
+    /// other patterns ("/static/**") against the path of the file from the root folder.
+    /// "*" matches any characters except '/', "?" matches one character, "**" matches any number of folders.
+    pub fn new(cache_rules: &[config::CacheRule]) -> Self {
+        let rules = cache_rules
+            .iter()
+            .map(|cache_rule| {
+                let pattern = cache_rule.pattern.trim_start_matches('/');
+                Rule {
+                    pattern_segments: pattern.split('/').map(str::to_string).collect(),
+                    match_file_name: !cache_rule.pattern.contains('/'),
+                    cache_control: cache_rule.cache_control.to_string(),
+                    max_age: max_age(cache_rule.cache_control),
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Finds the Cache-Control value and max-age for the file, the path is relative to the root folder
+    pub fn find(&self, relative_path: &path::Path) -> Option<(&str, Option<u64>)> {
+        let segments: Vec<String> = relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let file_name = segments.last()?;
+        self.rules
+            .iter()
+            .find(|rule| {
+                if rule.match_file_name {
+                    segment_matches(rule.pattern_segments[0].as_bytes(), file_name.as_bytes())
+                } else {
+                    path_matches(&rule.pattern_segments, &segments)
+                }
+            })
+            .map(|rule| (rule.cache_control.as_str(), rule.max_age))
+    }
+}
+
+/// Extracts max-age directive from the Cache-Control value.
+/// The value is checked when the server is configured, it is at most config::MAX_AGE_LIMIT.
+fn max_age(cache_control: &str) -> Option<u64> {
+    cache_control.split(',').find_map(|directive| {
+        let (name, value) = directive.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("max-age") {
+            return None;
+        }
+        value.trim().trim_matches('"').parse().ok()
+    })
+}
+
+/// Matches the path segments against the pattern segments, "**" matches any number of segments
+fn path_matches(pattern_segments: &[String], segments: &[String]) -> bool {
+    glob_matches(
+        pattern_segments,
+        segments,
+        |pattern_segment| pattern_segment == "**",
+        |pattern_segment, segment| segment_matches(pattern_segment.as_bytes(), segment.as_bytes()),
+    )
+}
+
+/// Matches one segment against the pattern with "*" and "?" wildcards
+fn segment_matches(pattern: &[u8], segment: &[u8]) -> bool {
+    glob_matches(
+        pattern,
+        segment,
+        |pattern_byte| *pattern_byte == b'*',
+        |pattern_byte, byte| *pattern_byte == b'?' || pattern_byte == byte,
+    )
+}
+
+/// Matches the items against the pattern with the star items matching any number of items.
+/// On a mismatch only the last star takes one more item, so the time is linear in the pattern length
+/// times the number of items, not exponential in the number of stars.
+fn glob_matches<P, T>(
+    pattern: &[P],
+    items: &[T],
+    is_star: impl Fn(&P) -> bool,
+    item_matches: impl Fn(&P, &T) -> bool,
+) -> bool {
+    let (mut pattern_index, mut item_index) = (0, 0);
+    // Pattern position after the last star and the number of items it has taken
+    let mut last_star: Option<(usize, usize)> = None;
+    while item_index < items.len() {
+        match pattern.get(pattern_index) {
+            Some(pattern_item) if is_star(pattern_item) => {
+                pattern_index += 1;
+                last_star = Some((pattern_index, item_index));
+            }
+            Some(pattern_item) if item_matches(pattern_item, &items[item_index]) => {
+                pattern_index += 1;
+                item_index += 1;
+            }
+            _ => {
+                let Some((star_pattern_index, star_item_index)) = last_star else {
+                    return false;
+                };
+                pattern_index = star_pattern_index;
+                item_index = star_item_index + 1;
+                last_star = Some((star_pattern_index, item_index));
+            }
+        }
+    }
+    // The rest of the pattern may only consist of stars matching nothing
+    pattern[pattern_index..].iter().all(is_star)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> CacheRules {
+        CacheRules::new(&[
+            config::CacheRule {
+                pattern: "/static/**",
+                cache_control: "public, immutable, max-age=31536000",
+            },
+            config::CacheRule {
+                pattern: "*.html",
+                cache_control: "no-cache",
+            },
+            config::CacheRule {
+                pattern: "/docs/*/v?.css",
+                cache_control: "max-age=60",
+            },
+        ])
+    }
+
+    fn find(relative_path: &str) -> Option<(String, Option<u64>)> {
+        rules()
+            .find(path::Path::new(relative_path))
+            .map(|(cache_control, max_age)| (cache_control.to_string(), max_age))
+    }
+
+    fn found(cache_control: &str, max_age: Option<u64>) -> Option<(String, Option<u64>)> {
+        Some((cache_control.to_string(), max_age))
+    }
+
+    #[test]
+    fn matching_rules() {
+        let immutable = found("public, immutable, max-age=31536000", Some(31536000));
+        assert_eq!(find("static/app.3f2a.js"), immutable);
+        assert_eq!(find("static/img/logo.png"), immutable);
+        // The first matching rule is applied
+        assert_eq!(find("static/index.html"), immutable);
+        assert_eq!(find("index.html"), found("no-cache", None));
+        assert_eq!(find("docs/guide/index.html"), found("no-cache", None));
+        assert_eq!(find("docs/guide/v2.css"), found("max-age=60", Some(60)));
+        assert_eq!(find("docs/guide/v10.css"), None);
+        assert_eq!(find("docs/v2.css"), None);
+        assert_eq!(find("staticfile.js"), None);
+        assert_eq!(find(""), None);
+    }
+
+    #[test]
+    fn max_age_directive() {
+        assert_eq!(max_age("max-age=60"), Some(60));
+        assert_eq!(max_age("public, MAX-AGE = \"3600\""), Some(3600));
+        assert_eq!(max_age("s-maxage=60, no-cache"), None);
+        assert_eq!(max_age("max-age=-1"), None);
+        assert_eq!(max_age("max-age=2147483648"), Some(2147483648));
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(segment_matches(b"*.js", b"app.js"));
+        assert!(segment_matches(b"a*b*c", b"aXbYbZc"));
+        assert!(segment_matches(b"v?.*", b"v2.css"));
+        assert!(segment_matches(b"**", b""));
+        assert!(!segment_matches(b"*.js", b"app.json"));
+        assert!(!segment_matches(b"v?.css", b"v.css"));
+
+        let segments = |path: &str| path.split('/').map(str::to_string).collect::<Vec<_>>();
+        assert!(path_matches(&segments("**/a/**/b"), &segments("a/x/y/b")));
+        assert!(path_matches(&segments("x/**"), &segments("x")));
+        assert!(!path_matches(&segments("**/a/**/b"), &segments("a/x/y/c")));
+
+        // Many stars do not make the matching exponential
+        let pattern = "*a".repeat(30) + "b";
+        assert!(!segment_matches(
+            pattern.as_bytes(),
+            "a".repeat(1000).as_bytes()
+        ));
+        let pattern = "**/a/".repeat(30) + "b";
+        let path = "a/".repeat(500) + "c";
+        assert!(!path_matches(&segments(&pattern), &segments(&path)));
+    }
+}
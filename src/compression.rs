@@ -0,0 +1,260 @@
+/// Content codings (RFC 9110, section 8.4.1) and their negotiation
+use crate::request;
+use std::{io, io::Write};
+
+/// Compression level of gzip and deflate, the default of zlib
+const DEFLATE_LEVEL: u32 = 6;
+/// Brotli quality, the higher levels are too slow for the compression on the fly
+const BROTLI_QUALITY: u32 = 5;
+/// Brotli window size (log2), the recommended default
+const BROTLI_WINDOW_BITS: u32 = 22;
+const BROTLI_BUFFER_SIZE: usize = 64 * 1024;
+
+/// MIME types whose content compresses well, besides "text/*", "+json" and "+xml" types
+const COMPRESSIBLE_MIME_TYPES: [&str; 8] = [
+    "application/javascript",
+    "application/json",
+    "application/xml",
+    "application/wasm",
+    "application/vnd.ms-fontobject",
+    "image/x-icon",
+    "font/ttf",
+    "font/otf",
+];
+
+/// Content coding supported by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    /// "deflate" is the zlib format (RFC 1950), not the raw deflate stream
+    Deflate,
+    /// Zstandard is only served from the precompressed files, it is not compressed on the fly
+    Zstd,
+}
+
+impl Encoding {
+    /// Encodings applied on the fly, in order of preference.
+    /// The order is used when the client accepts several of them with the same quality.
+    pub const ON_THE_FLY: [Self; 3] = [Self::Brotli, Self::Gzip, Self::Deflate];
+    /// Encodings of the precompressed sibling files, in order of preference
+    pub const PRECOMPRESSED: [Self; 3] = [Self::Brotli, Self::Zstd, Self::Gzip];
+
+    /// Name used in Accept-Encoding and Content-Encoding headers
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// Extension of the precompressed sibling file, "app.js.br" for "app.js" for example
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gz",
+            Self::Deflate => "zz",
+            Self::Zstd => "zst",
+        }
+    }
+}
+
+/// Chooses one of the available encodings (in order of preference) according to the Accept-Encoding header of the request.
+/// Returns None if the client does not accept any of them or prefers the identity (uncompressed) body.
+pub fn negotiate(request: &request::Request, encodings: &[Encoding]) -> Option<Encoding> {
+    // No Accept-Encoding means that any encoding is acceptable, but clients that do not send it
+    // are usually simple tools that do not expect compressed bodies
+    request.headers.get("Accept-Encoding")?;
+    let qualities: Vec<(&str, f32)> = request
+        .headers
+        .get_quality_list("Accept-Encoding")
+        .collect();
+    let explicit_quality = |name: &str| {
+        qualities
+            .iter()
+            .find(|(coding, _)| coding.eq_ignore_ascii_case(name))
+            .map(|(_, quality)| *quality)
+    };
+    let any_quality = explicit_quality("*");
+    let quality_of = |encoding: Encoding| {
+        let quality = match encoding {
+            // "x-gzip" is an alias of "gzip" (RFC 9110, section 8.4.1.3)
+            Encoding::Gzip => explicit_quality("gzip").or_else(|| explicit_quality("x-gzip")),
+            encoding => explicit_quality(encoding.as_str()),
+        };
+        quality.or(any_quality).unwrap_or(0.0)
+    };
+    // Identity is acceptable unless it is excluded explicitly or by "*;q=0"
+    let identity_quality = explicit_quality("identity").or(any_quality).unwrap_or(1.0);
+
+    let mut best: Option<(Encoding, f32)> = None;
+    for &encoding in encodings {
+        let quality = quality_of(encoding);
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((encoding, quality));
+        }
+    }
+    match best {
+        Some((encoding, quality)) if quality >= identity_quality => Some(encoding),
+        _ => None,
+    }
+}
+
+/// Checks if the content of the MIME type is worth compressing.
+/// Most images, audio, video and archives are already compressed.
+pub fn is_compressible(content_type: &str) -> bool {
+    let mime_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime_type.starts_with("text/")
+        || mime_type.ends_with("+json")
+        || mime_type.ends_with("+xml")
+        || COMPRESSIBLE_MIME_TYPES.contains(&mime_type.as_str())
+}
+
+/// Writer compressing the data written to it, supports the encodings from Encoding::ON_THE_FLY
+pub enum Encoder<W: Write> {
+    Brotli(Box<brotli::CompressorWriter<W>>),
+    Gzip(flate2::write::GzEncoder<W>),
+    Deflate(flate2::write::ZlibEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(encoding: Encoding, writer: W) -> Result<Self, io::Error> {
+        let encoder = match encoding {
+            Encoding::Brotli => Self::Brotli(Box::new(brotli::CompressorWriter::new(
+                writer,
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_WINDOW_BITS,
+            ))),
+            Encoding::Gzip => Self::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::new(DEFLATE_LEVEL),
+            )),
+            Encoding::Deflate => Self::Deflate(flate2::write::ZlibEncoder::new(
+                writer,
+                flate2::Compression::new(DEFLATE_LEVEL),
+            )),
+            Encoding::Zstd => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "zstd is served only from precompressed files",
+                ))
+            }
+        };
+        Ok(encoder)
+    }
+
+    /// Writes the end of the compressed stream and returns the inner writer
+    pub fn finish(self) -> Result<W, io::Error> {
+        match self {
+            Self::Brotli(encoder) => {
+                let mut encoder = *encoder;
+                encoder.flush()?;
+                Ok(encoder.into_inner())
+            }
+            Self::Gzip(encoder) => encoder.finish(),
+            Self::Deflate(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Brotli(encoder) => encoder.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Deflate(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Brotli(encoder) => encoder.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Deflate(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Compresses the bytes in memory
+pub fn encode(encoding: Encoding, bytes: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let mut encoder = Encoder::new(encoding, Vec::new())?;
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn negotiate_with(accept_encoding: &str) -> Option<Encoding> {
+        let head = format!("GET / HTTP/1.0\r\nAccept-Encoding: {accept_encoding}\r\n\r\n");
+        negotiate(&request::parse(&head).unwrap(), &Encoding::ON_THE_FLY)
+    }
+
+    #[test]
+    fn negotiation() {
+        assert_eq!(negotiate_with("gzip, deflate, br"), Some(Encoding::Brotli));
+        assert_eq!(negotiate_with("gzip, deflate"), Some(Encoding::Gzip));
+        assert_eq!(negotiate_with("x-gzip"), Some(Encoding::Gzip));
+        assert_eq!(negotiate_with("br;q=0.5, deflate"), Some(Encoding::Deflate));
+        assert_eq!(negotiate_with("*"), Some(Encoding::Brotli));
+        assert_eq!(negotiate_with("*, br;q=0"), Some(Encoding::Gzip));
+        assert_eq!(negotiate_with("gzip;q=0.5, identity"), None);
+        assert_eq!(negotiate_with("gzip;q=0"), None);
+        assert_eq!(negotiate_with("compress"), None);
+        assert_eq!(negotiate_with(""), None);
+        let request = request::parse("GET / HTTP/1.0\r\n\r\n").unwrap();
+        assert_eq!(negotiate(&request, &Encoding::ON_THE_FLY), None);
+        // Only the available encodings are chosen
+        let request =
+            request::parse("GET / HTTP/1.0\r\nAccept-Encoding: br, zstd\r\n\r\n").unwrap();
+        assert_eq!(
+            negotiate(&request, &[Encoding::Zstd, Encoding::Gzip]),
+            Some(Encoding::Zstd)
+        );
+        assert_eq!(negotiate(&request, &[Encoding::Gzip]), None);
+    }
+
+    #[test]
+    fn compressible_mime_types() {
+        assert!(is_compressible("text/html; charset=utf-8"));
+        assert!(is_compressible("application/JSON"));
+        assert!(is_compressible("image/svg+xml"));
+        assert!(is_compressible("application/ld+json"));
+        assert!(!is_compressible("image/png"));
+        assert!(!is_compressible("application/octet-stream"));
+    }
+
+    #[test]
+    fn encoded_bytes_are_decodable() {
+        let content = "Simple Web Server ".repeat(1000).into_bytes();
+        for encoding in Encoding::ON_THE_FLY {
+            let encoded = encode(encoding, &content).unwrap();
+            assert!(encoded.len() < content.len() / 10, "{encoding:?}");
+            let mut decoded = Vec::new();
+            match encoding {
+                Encoding::Brotli => brotli::Decompressor::new(encoded.as_slice(), 4096)
+                    .read_to_end(&mut decoded)
+                    .unwrap(),
+                Encoding::Gzip => flate2::read::GzDecoder::new(encoded.as_slice())
+                    .read_to_end(&mut decoded)
+                    .unwrap(),
+                Encoding::Deflate => flate2::read::ZlibDecoder::new(encoded.as_slice())
+                    .read_to_end(&mut decoded)
+                    .unwrap(),
+                Encoding::Zstd => unreachable!(),
+            };
+            assert_eq!(decoded, content, "{encoding:?}");
+        }
+        assert!(encode(Encoding::Zstd, &content).is_err());
+    }
+}
@@ -0,0 +1,464 @@
+/// Validators of the served files and the conditional request headers (RFC 9110, section 13)
+use crate::{config, date_time, request};
+use std::{collections::HashMap, fmt, fs, io, io::Read, io::Seek, path, sync, time};
+
+/// Maximum number of the remembered content hashes, the cache is cleared when it is full
+const MAX_CONTENT_HASHES_NUMBER: usize = 4096;
+/// Size of the chunks in which the file is read while hashing
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Entity tag (RFC 9110, section 8.8.3)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTag {
+    pub weak: bool,
+    /// Tag without the quotes
+    pub opaque: String,
+}
+
+impl EntityTag {
+    /// Parses the comma-separated list of entity tags, parsing stops at the first malformed member
+    pub fn parse_list(value: &str) -> Vec<Self> {
+        let mut entity_tags = Vec::new();
+        let mut rest = value;
+        loop {
+            rest = rest.trim_start_matches([' ', '\t', ',']);
+            if rest.is_empty() {
+                return entity_tags;
+            }
+            let Some((entity_tag, tail)) = Self::parse_prefix(rest) else {
+                return entity_tags;
+            };
+            entity_tags.push(entity_tag);
+            rest = tail;
+        }
+    }
+
+    /// Parses the single entity tag, the whole value must be the tag
+    pub fn parse(value: &str) -> Option<Self> {
+        match Self::parse_prefix(value.trim()) {
+            Some((entity_tag, "")) => Some(entity_tag),
+            _ => None,
+        }
+    }
+
+    /// Parses the entity tag at the beginning of the string, returns the tag and the rest of the string
+    fn parse_prefix(string: &str) -> Option<(Self, &str)> {
+        let (weak, string) = match string.strip_prefix("W/") {
+            Some(string) => (true, string),
+            None => (false, string),
+        };
+        let string = string.strip_prefix('"')?;
+        let end = string.find('"')?;
+        let opaque = &string[..end];
+        // etagc = %x21 / %x23-7E / obs-text
+        if opaque.bytes().any(|byte| byte <= 0x20 || byte == 0x7F) {
+            return None;
+        }
+        let entity_tag = Self {
+            weak,
+            opaque: opaque.to_string(),
+        };
+        Some((entity_tag, &string[end + 1..]))
+    }
+
+    /// Strong comparison: both tags are strong and equal
+    pub fn strong_eq(&self, other: &Self) -> bool {
+        !self.weak && !other.weak && self.opaque == other.opaque
+    }
+
+    /// Weak comparison: the tags are equal regardless of their weakness
+    pub fn weak_eq(&self, other: &Self) -> bool {
+        self.opaque == other.opaque
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            f.write_str("W/")?;
+        }
+        write!(f, "\"{}\"", self.opaque)
+    }
+}
+
+/// Validators of the file, sent in the ETag and Last-Modified headers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    pub entity_tag: EntityTag,
+    /// Modification time of the file, if the platform provides it
+    pub last_modified: Option<date_time::DateTime>,
+}
+
+impl Validators {
+    /// Creates the validators of the file.
+    /// The entity tag is derived from the inode, the modification time and the size of the file,
+    /// or from the hash of the content if entity_tag_kind is ContentHash.
+    pub fn new(
+        file: &fs::File,
+        path: &path::Path,
+        metadata: &fs::Metadata,
+        entity_tag_kind: config::EntityTagKind,
+        content_hashes: &ContentHashes,
+    ) -> Result<Self, io::Error> {
+        let modified = metadata.modified().ok();
+        let entity_tag = match entity_tag_kind {
+            config::EntityTagKind::Strong | config::EntityTagKind::Weak => EntityTag {
+                weak: entity_tag_kind == config::EntityTagKind::Weak,
+                opaque: metadata_tag(metadata),
+            },
+            config::EntityTagKind::ContentHash => EntityTag {
+                weak: false,
+                opaque: format!("{:016x}", content_hashes.get(file, path, metadata)?),
+            },
+        };
+        Ok(Self {
+            entity_tag,
+            last_modified: modified.map(date_time::DateTime::from_system_time),
+        })
+    }
+}
+
+/// Forms the entity tag from the inode, the modification time and the size of the file
+fn metadata_tag(metadata: &fs::Metadata) -> String {
+    let modified_nanos = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+    #[cfg(unix)]
+    let inode = std::os::unix::fs::MetadataExt::ino(metadata);
+    #[cfg(not(unix))]
+    let inode = 0;
+    format!("{inode:x}-{modified_nanos:x}-{:x}", metadata.len())
+}
+
+/// Hashes of the file contents.
+/// A hash is computed once and reused while the metadata tag (inode, modification time and size) of the file is the same.
+#[derive(Default)]
+pub struct ContentHashes {
+    hashes: sync::Mutex<HashMap<path::PathBuf, (String, u64)>>,
+}
+
+impl ContentHashes {
+    /// Returns the hash of the file content, the file position is restored to the beginning
+    fn get(
+        &self,
+        file: &fs::File,
+        path: &path::Path,
+        metadata: &fs::Metadata,
+    ) -> Result<u64, io::Error> {
+        let tag = metadata_tag(metadata);
+        if let Some((cached_tag, hash)) = self.lock().get(path) {
+            if *cached_tag == tag {
+                return Ok(*hash);
+            }
+        }
+        // The lock is not held while the file is read
+        let hash = hash_content(file)?;
+        let mut hashes = self.lock();
+        if hashes.len() >= MAX_CONTENT_HASHES_NUMBER {
+            hashes.clear();
+        }
+        hashes.insert(path.to_path_buf(), (tag, hash));
+        Ok(hash)
+    }
+
+    fn lock(&self) -> sync::MutexGuard<'_, HashMap<path::PathBuf, (String, u64)>> {
+        // The map is always consistent, so the poisoned lock can be used
+        self.hashes
+            .lock()
+            .unwrap_or_else(sync::PoisonError::into_inner)
+    }
+}
+
+/// Computes the 64-bit FNV-1a hash of the file content from the beginning and rewinds the file
+fn hash_content(mut file: &fs::File) -> Result<u64, io::Error> {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut buffer = vec![0; HASH_CHUNK_SIZE];
+    file.rewind()?;
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        for byte in &buffer[..read] {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    file.rewind()?;
+    Ok(hash)
+}
+
+/// Result of the evaluation of the preconditions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// The request should be served as usual
+    Passed,
+    /// 304 Not Modified
+    NotModified,
+    /// 412 Precondition Failed
+    Failed,
+}
+
+/// Evaluates If-Match, If-Unmodified-Since, If-None-Match and If-Modified-Since headers
+/// in the order defined by RFC 9110, section 13.2.2. If-Range is evaluated separately, see is_range_applicable.
+pub fn evaluate(request: &request::Request, validators: &Validators) -> Precondition {
+    let is_get_or_head = matches!(request.method, request::Method::Get | request::Method::Head);
+    let last_modified = validators
+        .last_modified
+        .map(date_time::DateTime::to_unix_seconds);
+    let header_date = |name: &str| {
+        request
+            .headers
+            .get(name)
+            .and_then(date_time::DateTime::parse_http_date)
+            .map(date_time::DateTime::to_unix_seconds)
+    };
+
+    if let Some(if_match) = request.headers.get("If-Match") {
+        let matches = if_match.trim() == "*"
+            || EntityTag::parse_list(if_match)
+                .iter()
+                .any(|entity_tag| entity_tag.strong_eq(&validators.entity_tag));
+        if !matches {
+            return Precondition::Failed;
+        }
+    } else if let (Some(date), Some(last_modified)) =
+        (header_date("If-Unmodified-Since"), last_modified)
+    {
+        if last_modified > date {
+            return Precondition::Failed;
+        }
+    }
+
+    if let Some(if_none_match) = request.headers.get("If-None-Match") {
+        let matches = if_none_match.trim() == "*"
+            || EntityTag::parse_list(if_none_match)
+                .iter()
+                .any(|entity_tag| entity_tag.weak_eq(&validators.entity_tag));
+        if matches {
+            return if is_get_or_head {
+                Precondition::NotModified
+            } else {
+                Precondition::Failed
+            };
+        }
+    } else if let (true, Some(date), Some(last_modified)) = (
+        is_get_or_head,
+        header_date("If-Modified-Since"),
+        last_modified,
+    ) {
+        if last_modified <= date {
+            return Precondition::NotModified;
+        }
+    }
+
+    Precondition::Passed
+}
+
+/// Evaluates the If-Range header (RFC 9110, section 13.1.5).
+/// Returns true if the Range header should be applied: If-Range is absent or its validator matches the file.
+/// The entity tag must match by the strong comparison, the date must be equal to the modification time.
+pub fn is_range_applicable(request: &request::Request, validators: &Validators) -> bool {
+    let Some(if_range) = request.headers.get("If-Range") else {
+        return true;
+    };
+    if let Some(entity_tag) = EntityTag::parse(if_range) {
+        return entity_tag.strong_eq(&validators.entity_tag);
+    }
+    match (
+        date_time::DateTime::parse_http_date(if_range),
+        validators.last_modified,
+    ) {
+        (Some(date), Some(last_modified)) => date == last_modified,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAST_MODIFIED: &str = "Sun, 06 Nov 1994 08:49:37 GMT";
+
+    fn validators() -> Validators {
+        Validators {
+            entity_tag: EntityTag {
+                weak: false,
+                opaque: "1f-5a-400".to_string(),
+            },
+            last_modified: Some(date_time::DateTime::from_unix_seconds(784111777)),
+        }
+    }
+
+    fn request_with(method: &str, header: &str) -> request::Request {
+        let head = format!("{method} / HTTP/1.0\r\nRange: bytes=0-1\r\n{header}\r\n\r\n");
+        request::parse(&head).unwrap()
+    }
+
+    fn evaluate_with(method: &str, header: &str) -> Precondition {
+        evaluate(&request_with(method, header), &validators())
+    }
+
+    #[test]
+    fn entity_tags() {
+        assert_eq!(
+            EntityTag::parse_list(" \"a\", W/\"b\" ,,\"\""),
+            [
+                EntityTag {
+                    weak: false,
+                    opaque: "a".to_string()
+                },
+                EntityTag {
+                    weak: true,
+                    opaque: "b".to_string()
+                },
+                EntityTag {
+                    weak: false,
+                    opaque: String::new()
+                },
+            ]
+        );
+        assert_eq!(EntityTag::parse_list("\"a\", b, \"c\"").len(), 1);
+        assert_eq!(EntityTag::parse("W/\"b\"").unwrap().to_string(), "W/\"b\"");
+        assert_eq!(EntityTag::parse("\"a b\""), None);
+        assert_eq!(EntityTag::parse("\"a\"\"b\""), None);
+        let strong = EntityTag::parse("\"a\"").unwrap();
+        let weak = EntityTag::parse("W/\"a\"").unwrap();
+        assert!(strong.strong_eq(&strong) && !strong.strong_eq(&weak));
+        assert!(strong.weak_eq(&weak) && weak.weak_eq(&weak));
+    }
+
+    #[test]
+    fn if_match_and_if_unmodified_since() {
+        assert_eq!(evaluate_with("GET", "If-Match: *"), Precondition::Passed);
+        assert_eq!(
+            evaluate_with("PUT", "If-Match: \"x\", \"1f-5a-400\""),
+            Precondition::Passed
+        );
+        assert_eq!(
+            evaluate_with("GET", "If-Match: W/\"1f-5a-400\""),
+            Precondition::Failed
+        );
+        assert_eq!(
+            evaluate_with("GET", &format!("If-Unmodified-Since: {LAST_MODIFIED}")),
+            Precondition::Passed
+        );
+        assert_eq!(
+            evaluate_with("GET", "If-Unmodified-Since: Sun, 06 Nov 1994 08:49:36 GMT"),
+            Precondition::Failed
+        );
+        // Invalid date is ignored
+        assert_eq!(
+            evaluate_with("GET", "If-Unmodified-Since: yesterday"),
+            Precondition::Passed
+        );
+    }
+
+    #[test]
+    fn if_none_match_and_if_modified_since() {
+        assert_eq!(
+            evaluate_with("GET", "If-None-Match: W/\"1f-5a-400\""),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            evaluate_with("HEAD", "If-None-Match: *"),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            evaluate_with("PUT", "If-None-Match: *"),
+            Precondition::Failed
+        );
+        assert_eq!(
+            evaluate_with("GET", "If-None-Match: \"x\""),
+            Precondition::Passed
+        );
+        assert_eq!(
+            evaluate_with("GET", &format!("If-Modified-Since: {LAST_MODIFIED}")),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            evaluate_with("GET", "If-Modified-Since: Sun, 06 Nov 1994 08:49:36 GMT"),
+            Precondition::Passed
+        );
+        // If-Modified-Since is ignored when If-None-Match is present
+        assert_eq!(
+            evaluate_with(
+                "GET",
+                &format!("If-None-Match: \"x\"\r\nIf-Modified-Since: {LAST_MODIFIED}")
+            ),
+            Precondition::Passed
+        );
+    }
+
+    #[test]
+    fn if_range() {
+        let is_range_applicable_with =
+            |header: &str| is_range_applicable(&request_with("GET", header), &validators());
+        assert!(is_range_applicable_with("Accept: */*"));
+        assert!(is_range_applicable_with("If-Range: \"1f-5a-400\""));
+        assert!(is_range_applicable_with(&format!(
+            "If-Range: {LAST_MODIFIED}"
+        )));
+        assert!(!is_range_applicable_with("If-Range: \"1f-5a-401\""));
+        assert!(!is_range_applicable_with("If-Range: W/\"1f-5a-400\""));
+        assert!(!is_range_applicable_with(
+            "If-Range: Sun, 06 Nov 1994 08:49:38 GMT"
+        ));
+        assert!(!is_range_applicable_with("If-Range: yesterday"));
+    }
+
+    #[test]
+    fn absurd_dates_are_ignored() {
+        // The far future date would make the file not modified, the far past one would fail the precondition
+        for header in [
+            "If-Modified-Since: Sun, 06 Nov 999999999999 08:49:37 GMT",
+            "If-Modified-Since: Sun, 06 Nov 9223372036854775807 08:49:37 GMT",
+            "If-Unmodified-Since: Sun, 06 Nov -999999999999 08:49:37 GMT",
+            "If-Unmodified-Since: Sun, 06 Nov 0 08:49:37 GMT",
+        ] {
+            assert_eq!(
+                evaluate_with("GET", header),
+                Precondition::Passed,
+                "{header}"
+            );
+        }
+        assert!(!is_range_applicable(
+            &request_with("GET", "If-Range: Sun, 06 Nov 999999999999 08:49:37 GMT"),
+            &validators()
+        ));
+    }
+
+    #[test]
+    fn entity_tag_kinds() {
+        let path = std::env::temp_dir().join("simple_web_server_conditional_entity_tag.txt");
+        let content_hashes = ContentHashes::default();
+        let validators_of = |content: &str, entity_tag_kind| {
+            fs::write(&path, content).unwrap();
+            let file = fs::File::open(&path).unwrap();
+            let metadata = file.metadata().unwrap();
+            Validators::new(&file, &path, &metadata, entity_tag_kind, &content_hashes).unwrap()
+        };
+        let first = validators_of("first", config::EntityTagKind::Strong);
+        let second = validators_of("second", config::EntityTagKind::Strong);
+        let weak = validators_of("second", config::EntityTagKind::Weak);
+        let hash = validators_of("second", config::EntityTagKind::ContentHash);
+        let same_hash = validators_of("second", config::EntityTagKind::ContentHash);
+        let other_hash = validators_of("third", config::EntityTagKind::ContentHash);
+        let _ = fs::remove_file(&path);
+
+        assert_ne!(first.entity_tag, second.entity_tag);
+        assert!(weak.entity_tag.weak && !second.entity_tag.weak);
+        // FNV-1a of "second"
+        assert_eq!(
+            hash.entity_tag.opaque,
+            format!("{:016x}", 0xa499_85ef_4cee_20bdu64)
+        );
+        assert_eq!(hash.entity_tag, same_hash.entity_tag);
+        assert_ne!(hash.entity_tag, other_hash.entity_tag);
+    }
+}
@@ -1,19 +1,162 @@
-use std::{net, path};
+use std::{io, net, path, time};
 
 /// Server config
 #[derive(Debug)]
 pub struct Config<'a> {
-    pub socket_addr_v4: net::SocketAddrV4,
+    /// Addresses the server is listening on, at least one is required.
+    /// The first one is the main address, it has the common root folder and TLS settings.
+    pub listeners: Vec<Listener<'a>>,
+    /// Should the IPv6 listeners accept IPv4 connections too (IPV6_V6ONLY is off)
+    pub dual_stack: bool,
+    /// Should the listening sockets passed by systemd (LISTEN_FDS) be used instead of binding the addresses.
+    /// The passed sockets bound to the listener addresses get the settings of the listeners, others get the common settings.
+    /// Every listener except the main one must have its passed socket.
+    pub socket_activation: bool,
+    /// Unix domain socket the server is listening on in addition to the TCP listeners, for a local reverse proxy
+    pub unix_socket: Option<UnixSocket<'a>>,
+    /// Root folder of the listeners that have no own root folder
     pub root_folder_path: &'a path::Path,
     pub threads_number: u8,
+    /// Optional file with additional extension to MIME type mappings (in the "mime.types" format)
+    pub mime_types_file_path: Option<&'a path::Path>,
+    /// MIME type of files with unknown extensions
+    pub default_mime_type: &'a str,
+    /// Names of the files that are served when a folder is requested, in order of priority
+    pub index_file_names: Vec<&'a str>,
+    /// Should the folders without index file be listed
+    pub autoindex: bool,
+    /// Should the hidden files (names starting with '.') be listed
+    pub autoindex_show_hidden: bool,
+    /// How long an idle persistent connection waits for the next request, zero disables keep-alive
+    pub keep_alive_timeout: time::Duration,
+    /// Maximum number of requests served over one connection, zero disables keep-alive
+    pub max_requests_per_connection: u32,
+    /// Should the files be sent by sendfile(2), it is used only on Linux
+    pub use_sendfile: bool,
+    /// How the entity tags (ETag header) of the files are formed
+    pub entity_tag_kind: EntityTagKind,
+    /// Should the compressible responses be compressed (gzip, deflate or brotli) for the clients that accept it
+    pub compression: bool,
+    /// Bodies shorter than this number of bytes are not compressed
+    pub compression_min_size: u64,
+    /// Should the precompressed sibling files ("app.js.br", "app.js.zst", "app.js.gz" for "app.js") be served
+    pub precompressed: bool,
+    /// Cache-Control rules, the first rule whose pattern matches the served file is applied
+    pub cache_rules: Vec<CacheRule<'a>>,
+    /// Custom error pages, the built-in page is sent for other statuses or if the page file is missing
+    pub error_pages: Vec<ErrorPage<'a>>,
+    /// TLS certificates of the listeners that have no own TLS settings, HTTPS is served if it is not empty
+    pub tls_certificates: Vec<TlsCertificate<'a>>,
+    /// Plain HTTP listener redirecting all requests to HTTPS, it requires TLS
+    pub https_redirect: Option<HttpsRedirect>,
+    /// max-age of the Strict-Transport-Security header of the HTTPS responses, None disables the header
+    pub hsts_max_age: Option<u64>,
+    /// Should the Strict-Transport-Security header apply to the subdomains
+    pub hsts_include_subdomains: bool,
+}
+
+/// Plain HTTP listener answering every request with the redirect to the same URL over HTTPS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpsRedirect {
+    pub socket_addr: net::SocketAddr,
+    /// 301 or 308
+    pub status_code: u16,
+}
+
+/// Address the server is listening on, with the settings that differ from the common ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listener<'a> {
+    pub socket_addr: net::SocketAddr,
+    /// Root folder of the site served by the listener, Config::root_folder_path is used if it is None
+    pub root_folder_path: Option<&'a path::Path>,
+    /// TLS certificates of the listener, Config::tls_certificates are used if it is None.
+    /// Plain HTTP is served if it is empty.
+    pub tls_certificates: Option<Vec<TlsCertificate<'a>>>,
+}
+
+/// Unix domain socket listener, it serves plain HTTP from the common root folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnixSocket<'a> {
+    /// Path of the socket file, the stale file left by the previous run is removed before binding
+    pub path: &'a path::Path,
+    /// Permission bits of the socket file (0o660 for example), the umask decides them if it is None
+    pub mode: Option<u32>,
+}
+
+/// Certificate chain and private key (PEM files) for the server name requested by the client (SNI)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsCertificate<'a> {
+    /// "example.com" or "*.example.com", None for the default certificate
+    pub host_name: Option<&'a str>,
+    pub cert_chain_path: &'a path::Path,
+    pub private_key_path: &'a path::Path,
+}
+
+/// Custom page sent instead of the built-in page for the error status code (4xx or 5xx)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorPage<'a> {
+    pub status_code: u16,
+    /// Path of the page from the root folder, "/errors/404.html" for example
+    pub path: &'a str,
+}
+
+/// Greatest max-age (in seconds) of the Cache-Control rules, the rules with larger values are rejected.
+/// It is the value that RFC 9111 (section 1.2.2) recommends to send instead of the larger ones.
+pub const MAX_AGE_LIMIT: u64 = 1 << 31;
+
+/// Rule assigning the Cache-Control value to the files matching the glob pattern.
+/// Patterns without '/' ("*.html") are matched against the file name,
+/// other patterns ("/static/**") against the path of the file from the root folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheRule<'a> {
+    pub pattern: &'a str,
+    /// Value of the Cache-Control header, the Expires header is computed from its max-age directive
+    pub cache_control: &'a str,
+}
+
+/// Kind of the entity tags of the files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityTagKind {
+    /// Strong tag derived from the file metadata (inode, modification time and size)
+    Strong,
+    /// Weak tag derived from the file metadata, for files that may change without changing the content
+    Weak,
+    /// Strong tag derived from the hash of the file content, it is the same on all servers of the site
+    ContentHash,
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Wrong address.")]
     WrongAddr(#[from] net::AddrParseError),
+    #[error(
+        "Wrong listener, it must be in the format ADDR[;root=PATH][;tls=off|CERT_PATH,KEY_PATH]."
+    )]
+    WrongListener,
+    #[error("Failed to resolve the address: {0}")]
+    AddrResolutionError(io::Error),
+    #[error("Wrong Unix socket, the path is required and the mode must be octal permission bits (660 for example).")]
+    WrongUnixSocket,
     #[error("Wrong root folder path.")]
     WrongRootFolderPath,
     #[error("Zero threads number.")]
     ZeroThreadsNumber,
+    #[error("Wrong MIME types file path.")]
+    WrongMimeTypesFilePath,
+    #[error("Wrong default MIME type.")]
+    WrongDefaultMimeType,
+    #[error("Wrong index file name.")]
+    WrongIndexFileName,
+    #[error("Wrong ETag kind, it must be strong, weak or hash.")]
+    WrongEntityTagKind,
+    #[error("Wrong Cache-Control rule, it must be in the format PATTERN=VALUE, max-age must be at most 2147483648.")]
+    WrongCacheRule,
+    #[error("Wrong error page, it must be in the format CODE[,CODE...]=/PATH, the codes must be 400-599.")]
+    WrongErrorPage,
+    #[error("Wrong TLS certificate, the certificate and the private key files are required.")]
+    WrongTlsCertificate,
+    #[error("Wrong HTTPS redirect, it requires TLS and the status must be 301 or 308.")]
+    WrongHttpsRedirect,
+    #[error("Strict-Transport-Security requires TLS.")]
+    HstsWithoutTls,
 }
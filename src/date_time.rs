@@ -0,0 +1,298 @@
+/// Date and time in UTC, without third-party libraries
+use std::{fmt, time};
+
+const WEEKDAY_NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+/// Years of the parsed HTTP-dates, the dates come from the clients and other years are treated as invalid
+const HTTP_DATE_YEARS: std::ops::RangeInclusive<i64> = 1..=9999;
+
+/// Calendar date and time in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    /// 1..=12
+    pub month: u32,
+    /// 1..=31
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// 0 is Sunday
+    pub weekday: u32,
+}
+
+impl DateTime {
+    /// Converts the system time, the time before the Unix epoch is treated as the epoch
+    pub fn from_system_time(system_time: time::SystemTime) -> Self {
+        let seconds = system_time
+            .duration_since(time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        Self::from_unix_seconds(seconds)
+    }
+
+    /// Converts the number of seconds since the Unix epoch
+    pub fn from_unix_seconds(seconds: u64) -> Self {
+        let days = (seconds / 86400) as i64;
+        let seconds_of_day = (seconds % 86400) as u32;
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            hour: seconds_of_day / 3600,
+            minute: seconds_of_day % 3600 / 60,
+            second: seconds_of_day % 60,
+            // 1970-01-01 was Thursday
+            weekday: ((days + 4) % 7) as u32,
+        }
+    }
+
+    /// Returns the number of seconds since the Unix epoch, the time before the epoch is treated as the epoch.
+    /// The time that does not fit into i64 seconds is saturated.
+    pub fn to_unix_seconds(self) -> u64 {
+        let seconds = days_from_civil(self.year, self.month, self.day)
+            .and_then(|days| days.checked_mul(86400))
+            .and_then(|seconds| {
+                seconds.checked_add(
+                    i64::from(self.hour) * 3600
+                        + i64::from(self.minute) * 60
+                        + i64::from(self.second),
+                )
+            });
+        match seconds {
+            Some(seconds) => seconds.max(0) as u64,
+            None if self.year < 0 => 0,
+            None => i64::MAX as u64,
+        }
+    }
+
+    /// Formats the date and time as HTTP-date (RFC 9110, section 5.6.7): "Sun, 06 Nov 1994 08:49:37 GMT"
+    pub fn to_http_date(self) -> String {
+        format!(
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            WEEKDAY_NAMES[self.weekday as usize],
+            self.day,
+            MONTH_NAMES[self.month as usize - 1],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+
+    /// Parses HTTP-date in any of the three formats allowed by RFC 9110, section 5.6.7:
+    /// "Sun, 06 Nov 1994 08:49:37 GMT", "Sunday, 06-Nov-94 08:49:37 GMT" and "Sun Nov  6 08:49:37 1994".
+    pub fn parse_http_date(string: &str) -> Option<Self> {
+        let parts: Vec<&str> = string.split_whitespace().collect();
+        let (year, month, day, time) = match parts.as_slice() {
+            // IMF-fixdate
+            [_, day, month, year, time, "GMT"] => {
+                (year.parse().ok()?, *month, day.parse().ok()?, *time)
+            }
+            // Obsolete RFC 850 format
+            [_, date, time, "GMT"] => {
+                let mut date_parts = date.split('-');
+                let (Some(day), Some(month), Some(year), None) = (
+                    date_parts.next(),
+                    date_parts.next(),
+                    date_parts.next(),
+                    date_parts.next(),
+                ) else {
+                    return None;
+                };
+                let year: i64 = year.parse().ok()?;
+                // Two-digit year that appears to be more than 50 years in the future is in the past (RFC 9110, section 5.6.7)
+                let year = if year < 70 { 2000 + year } else { 1900 + year };
+                (year, month, day.parse().ok()?, *time)
+            }
+            // Obsolete asctime format
+            [_, month, day, time, year] => (year.parse().ok()?, *month, day.parse().ok()?, *time),
+            _ => return None,
+        };
+        let month = MONTH_NAMES.iter().position(|&name| name == month)? as u32 + 1;
+        let mut time_parts = time.split(':').map(|part| part.parse::<u32>().ok());
+        let (Some(Some(hour)), Some(Some(minute)), Some(Some(second)), None) = (
+            time_parts.next(),
+            time_parts.next(),
+            time_parts.next(),
+            time_parts.next(),
+        ) else {
+            return None;
+        };
+        if !HTTP_DATE_YEARS.contains(&year)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            return None;
+        }
+        let date_time = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            weekday: 0,
+        };
+        // Normalization fills the weekday and rejects the dates like "31 Feb"
+        let normalized = Self::from_unix_seconds(date_time.to_unix_seconds());
+        if normalized.day != day || normalized.month != month {
+            return None;
+        }
+        Some(normalized)
+    }
+}
+
+/// Formats the date and time in the ISO 8601 format: "2024-06-01T12:30:00Z"
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Converts the number of days since 1970-01-01 into (year, month, day).
+/// The algorithm is described in http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Converts (year, month, day) into the number of days since 1970-01-01, inverse of civil_from_days.
+/// Returns None if the number does not fit into i64.
+fn days_from_civil(year: i64, month: u32, day: u32) -> Option<i64> {
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era.checked_mul(146097)?.checked_add(day_of_era - 719468)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_epoch() {
+        let date_time = DateTime::from_unix_seconds(0);
+        assert_eq!(date_time.to_string(), "1970-01-01T00:00:00Z");
+        assert_eq!(date_time.weekday, 4);
+    }
+
+    #[test]
+    fn leap_day() {
+        // 2024-02-29 13:45:10, Thursday
+        let date_time = DateTime::from_unix_seconds(1709214310);
+        assert_eq!(date_time.to_string(), "2024-02-29T13:45:10Z");
+        assert_eq!(date_time.weekday, 4);
+    }
+
+    #[test]
+    fn http_date() {
+        let date_time = DateTime::from_unix_seconds(784111777);
+        assert_eq!(date_time.to_http_date(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(date_time.to_unix_seconds(), 784111777);
+        for string in [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+        ] {
+            assert_eq!(
+                DateTime::parse_http_date(string),
+                Some(date_time),
+                "{string}"
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_http_dates() {
+        for string in [
+            "",
+            "yesterday",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 31 Feb 1994 08:49:37 GMT",
+            "Sun, 06 Nox 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:49:37 GMT",
+            "Sun, 06 Nov 1994 08:49 GMT",
+        ] {
+            assert_eq!(DateTime::parse_http_date(string), None, "{string}");
+        }
+    }
+
+    #[test]
+    fn out_of_range_years() {
+        for string in [
+            "Sun, 06 Nov 999999999999 08:49:37 GMT",
+            "Sun, 06 Nov 9223372036854775807 08:49:37 GMT",
+            "Sun, 06 Nov -9223372036854775808 08:49:37 GMT",
+            "Sun, 06 Nov -1994 08:49:37 GMT",
+            "Sun, 06 Nov 0 08:49:37 GMT",
+            "Sun, 06 Nov 10000 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 999999999999",
+        ] {
+            assert_eq!(DateTime::parse_http_date(string), None, "{string}");
+        }
+        // The latest accepted year
+        assert_eq!(
+            DateTime::parse_http_date("Fri, 31 Dec 9999 23:59:59 GMT")
+                .unwrap()
+                .to_string(),
+            "9999-12-31T23:59:59Z"
+        );
+    }
+
+    #[test]
+    fn unix_seconds_saturation() {
+        let date_time = |year| DateTime {
+            year,
+            month: 11,
+            day: 6,
+            hour: 8,
+            minute: 49,
+            second: 37,
+            weekday: 0,
+        };
+        assert_eq!(date_time(i64::MAX).to_unix_seconds(), i64::MAX as u64);
+        assert_eq!(date_time(999999999999).to_unix_seconds(), i64::MAX as u64);
+        assert_eq!(date_time(i64::MIN).to_unix_seconds(), 0);
+        assert_eq!(date_time(-1994).to_unix_seconds(), 0);
+    }
+
+    #[test]
+    fn before_unix_epoch() {
+        let system_time = time::UNIX_EPOCH - time::Duration::from_secs(10);
+        assert_eq!(
+            DateTime::from_system_time(system_time),
+            DateTime::from_unix_seconds(0)
+        );
+    }
+}
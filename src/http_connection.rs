@@ -1,131 +1,644 @@
-use std::{fs, io, io::BufRead, net, path, string, sync, time};
+use crate::{
+    autoindex, cache_control, compression, conditional, config, mime, range, request, response,
+    site_path, stream,
+};
+use std::{collections, fs, io, io::BufRead, io::Seek, path, sync, time};
 
 const MAX_REQUEST_READ_SIZE: usize = 4096;
 const READ_TIMEOUT_MILLIS: u64 = 2000;
+/// Methods supported by the server, value of the Allow header
+const ALLOWED_METHODS: &str = "GET, HEAD, OPTIONS";
+/// Maximum size of the file that is compressed into memory for HTTP/1.0 clients
+const MAX_BUFFERED_ENCODING_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Settings shared by all HTTP connections.
+pub struct Settings {
+    /// Canonical path of the root folder
+    pub root_folder_path: path::PathBuf,
+    pub mime_types: mime::MimeTypes,
+    /// Names of the files that are served when a folder is requested, in order of priority
+    pub index_file_names: Vec<String>,
+    /// Should the folders without index file be listed
+    pub autoindex: bool,
+    /// Should the hidden files (names starting with '.') be listed
+    pub autoindex_show_hidden: bool,
+    /// How long an idle persistent connection waits for the next request
+    pub keep_alive_timeout: time::Duration,
+    /// Maximum number of requests served over one connection
+    pub max_requests_per_connection: u32,
+    /// Should the files be sent by sendfile(2) on Linux
+    pub use_sendfile: bool,
+    /// How the entity tags of the files are formed
+    pub entity_tag_kind: config::EntityTagKind,
+    /// Cache of the file content hashes, used if entity_tag_kind is ContentHash
+    pub content_hashes: conditional::ContentHashes,
+    /// Should the responses be compressed for the clients that accept it
+    pub compression: bool,
+    /// Bodies shorter than this number of bytes are not compressed
+    pub compression_min_size: u64,
+    /// Should the precompressed sibling files ("app.js.br" for "app.js") be served to the clients that accept them
+    pub precompressed: bool,
+    /// Cache-Control rules of the served files
+    pub cache_rules: cache_control::CacheRules,
+    /// Custom error pages: status code to the path of the page from the root folder ("/errors/404.html")
+    pub error_pages: collections::HashMap<u16, String>,
+    /// All requests are redirected to HTTPS, it is used by the plain HTTP listener
+    pub https_redirect: Option<HttpsRedirect>,
+    /// Value of the Strict-Transport-Security header, it is set only for the HTTPS connections
+    pub strict_transport_security: Option<String>,
+}
+
+/// Redirect of the plain HTTP requests to the same URL over HTTPS
+pub struct HttpsRedirect {
+    /// 301 Moved Permanently or 308 Permanent Redirect (the method and the body are kept by the client)
+    pub status: response::Status,
+    /// Port of the HTTPS listener, it is omitted from the URL if it is 443
+    pub https_port: u16,
+}
 
 /// HTTP connection.
 /// Manages the connection, parses the request and generates a response.
-pub struct HTTPConnection {
-    tcp_stream: net::TcpStream,
-    root_folder_path: path::PathBuf,
+/// The connection is generic over the stream type, so TCP, TLS and Unix domain socket connections are served the same way.
+pub struct HTTPConnection<S: stream::ConnectionStream>
+where
+    for<'a> &'a S: io::Read + io::Write,
+{
+    stream: S,
+    settings: sync::Arc<Settings>,
 }
 
-impl HTTPConnection {
-    pub fn new(tcp_stream: net::TcpStream, root_folder_path: sync::Arc<path::PathBuf>) -> Self {
-        let root_folder_path = sync::Arc::unwrap_or_clone(root_folder_path);
-        Self {
-            tcp_stream,
-            root_folder_path,
-        }
+impl<S: stream::ConnectionStream> HTTPConnection<S>
+where
+    for<'a> &'a S: io::Read + io::Write,
+{
+    pub fn new(stream: S, settings: sync::Arc<Settings>) -> Self {
+        Self { stream, settings }
     }
 
-    /// Checks and performs the HTTP connection
+    /// Checks and performs the HTTP connection.
+    /// Several requests can be served over one connection (keep-alive), including pipelined requests.
     pub fn perform(self) {
-        let mut stream = self.tcp_stream;
+        let stream = self.stream;
         // Thread will wait for a suitable HTTP request or until the amount of data exceeds MAX_REQUEST_READ_SIZE for an unlimited amount of time.
         // I don't need it, so the connection should be terminated if the data doesn't arrive within READ_TIMEOUT_MILLIS milliseconds.
         // Although, the client can still send a small amount of data (for example, 1 byte once per READ_TIMEOUT_MILLIS - 1 millisecond) and occupy the thread.
         // I do not know how to deal with this (it may be worth limiting the connection time in general).
         // It doesn't matter in this project.
         let _ = stream.set_read_timeout(Some(time::Duration::from_millis(READ_TIMEOUT_MILLIS)));
+        // The TLS handshake is limited by the same timeout
+        if let Err(error) = stream.handshake() {
+            if !is_timeout(&error) {
+                eprintln!(
+                    "Error in HTTP connection: {}",
+                    Error::TlsHandshakeError(error)
+                );
+            }
+            return;
+        }
+        // The same reader is used for all requests of the connection,
+        // so pipelined requests that are already in its buffer are not lost.
         let mut buf_reader = io::BufReader::new(&stream);
+        let mut served_requests_number: u32 = 0;
 
-        // Check and read request
-        let request = read_http_request(&mut buf_reader);
-        if let Err(ref error) = request {
-            if let Error::RequestReadError(error) = error {
-                // I'm not interested in timeout-related errors.
-                // This is a very common error, since some browsers initiate several (usually two) connections at once,
-                // the first is processed normally, but the second does not send anything (apparently this was done for optimizations).
-                if error.kind() == io::ErrorKind::TimedOut {
+        loop {
+            // Check and read request
+            let request = read_http_request(&mut buf_reader).and_then(|request| match request {
+                Some(request) => Ok(Some(request::parse(&request)?)),
+                None => Ok(None),
+            });
+            let request = match request {
+                Ok(Some(request)) => request,
+                // Client has closed the connection
+                Ok(None) => break,
+                Err(Error::RequestReadError(error)) if is_timeout(&error) => {
+                    // I'm not interested in timeout-related errors.
+                    // This is a very common error, since some browsers initiate several (usually two) connections at once,
+                    // the first is processed normally, but the second does not send anything (apparently this was done for optimizations).
+                    // It is also the usual end of an idle keep-alive connection.
                     // Exit without any message
-                    return;
+                    break;
                 }
+                Err(error) => {
+                    eprintln!("Error in HTTP connection: {error}");
+                    // The client is informed about the error, after that the connection is closed,
+                    // since it is unknown where the next request starts.
+                    let response = response::Response::error_page(error.status());
+                    let response = replace_error_page(&self.settings, response);
+                    let result = write_http_answer(&self.settings, &stream, &response, true);
+                    if let Err(error) = result {
+                        eprintln!("Error in HTTP connection: {error}");
+                    }
+                    break;
+                }
+            };
+            served_requests_number += 1;
+            // HTTP request has been read
+            //println!("request:\n\"{request:?}\"");
+
+            let response = answer_request(&self.settings, &request);
+            let mut response = replace_error_page(&self.settings, response);
+            // The answer to HEAD is the same as to GET, but without the body
+            let include_body = request.method != request::Method::Head;
+            let keep_alive = served_requests_number < self.settings.max_requests_per_connection
+                && is_keep_alive(&request);
+            response.set_keep_alive(keep_alive);
+            if keep_alive {
+                let keep_alive_header = format!(
+                    "timeout={}, max={}",
+                    self.settings.keep_alive_timeout.as_secs(),
+                    self.settings.max_requests_per_connection - served_requests_number
+                );
+                response.add_header("Keep-Alive", &keep_alive_header);
             }
-            eprintln!("Error in HTTP connection: {error}");
-            return;
+            if let Some(strict_transport_security) = &self.settings.strict_transport_security {
+                response.add_header("Strict-Transport-Security", strict_transport_security);
+            }
+
+            let result = write_http_answer(&self.settings, &stream, &response, include_body);
+            if let Err(error) = result {
+                eprintln!("Error in HTTP connection: {error}");
+                return;
+            }
+            if !keep_alive {
+                break;
+            }
+            // The next request is waited no longer than the keep-alive timeout
+            let _ = stream.set_read_timeout(Some(self.settings.keep_alive_timeout));
         }
-        let request = request.unwrap();
-        // HTTP request has been read
-        //println!("request:\n\"{request}\"");
-        //println!("request length: {}", request.len());
-        //println!("{path:?}");
-
-        // Prepare requested file path
-        // Root path + path from HTTP request
-        // Get root folder
-        let root_folder: path::PathBuf = self.root_folder_path.into();
-        // Get path from HTTP request
-        let http_requested_path = get_requested_path(&request);
-        if let Err(ref error) = http_requested_path {
+
+        let result = stream.shutdown().map_err(Error::ShutdownFailed);
+        if let Err(error) = result {
             eprintln!("Error in HTTP connection: {error}");
-            return;
         }
-        let mut http_requested_path = http_requested_path.unwrap();
-        // If a folder is requested, it should be returned index.html from this folder
-        if http_requested_path.is_dir() {
-            http_requested_path.push("index.html");
+    }
+}
+
+/// Checks if the connection should be kept open after answering the request.
+/// HTTP/1.1 connections are persistent by default, HTTP/1.0 connections only on request.
+fn is_keep_alive(request: &request::Request) -> bool {
+    // The body of the request is not read, so the next request can not be found after it
+    if request.headers.contains("Content-Length") || request.headers.contains("Transfer-Encoding") {
+        return false;
+    }
+    let mut connection_options = request.headers.get_list("Connection");
+    match request.version {
+        request::Version::Http10 => {
+            connection_options.any(|option| option.eq_ignore_ascii_case("keep-alive"))
         }
-        // Remove prefix "/" from http requested path
-        let http_requested_path = http_requested_path.strip_prefix("/");
-        if let Err(ref error) = http_requested_path {
-            eprintln!("Error in HTTP connection: {error}");
-            return;
+        request::Version::Http11 => {
+            !connection_options.any(|option| option.eq_ignore_ascii_case("close"))
         }
-        let http_requested_path = http_requested_path.unwrap();
-        // Root folder + path from HTTP
-        let full_path = root_folder.join(http_requested_path);
-
-        // Try to read requested file content
-        let requested_file_content: Option<String> = get_file_content(&full_path);
-        // Forms HTTP answer
-        let answer = form_http_answer(requested_file_content.as_ref());
-        //println!("answer:\n\"{answer}\"");
-
-        // Create BufWriter
-        let mut buf_writer = io::BufWriter::new(&mut stream);
-        // Write HTTP answer
-        use std::io::Write;
-
-        let result = buf_writer.write_all(answer.as_bytes());
-        if let Err(error) = result {
+    }
+}
+
+/// Checks if the error is caused by the read timeout.
+/// Depending on the platform, the timeout is reported as TimedOut or WouldBlock.
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
+
+/// Forms the answer to the request
+fn answer_request(settings: &Settings, request: &request::Request) -> response::Response {
+    if let Some(https_redirect) = &settings.https_redirect {
+        return redirect_to_https(https_redirect, request);
+    }
+    match request.method {
+        request::Method::Get | request::Method::Head => serve_file(settings, request),
+        request::Method::Options => {
+            let mut response = response::Response::new(response::Status::Ok);
+            response.add_header("Allow", ALLOWED_METHODS);
+            response
+        }
+        _ => {
+            let mut response = response::Response::error_page(response::Status::MethodNotAllowed);
+            response.add_header("Allow", ALLOWED_METHODS);
+            response
+        }
+    }
+}
+
+/// Forms the answer containing the requested file
+fn serve_file(settings: &Settings, request: &request::Request) -> response::Response {
+    // Prepare requested file path
+    // Normalized path relative to the root folder
+    let relative_path = site_path::normalize(&request.path);
+    if let Err(ref error) = relative_path {
+        eprintln!("Error in HTTP connection: {error}");
+        return response::Response::error_page(response::Status::Forbidden);
+    }
+    let relative_path = relative_path.unwrap();
+    // Root folder + path from HTTP, the file must be inside the root folder
+    let full_path = site_path::resolve(&settings.root_folder_path, &relative_path);
+    let full_path = match full_path {
+        Ok(Some(full_path)) if full_path.is_dir() => {
+            // Relative links in the index file are resolved against the folder only if the path ends with '/'
+            if !request.path.ends_with('/') {
+                return redirect_to_folder(request);
+            }
+            // If a folder is requested, it should be returned the first existing index file from this folder
+            let index_path = site_path::find_index(
+                &settings.root_folder_path,
+                &relative_path,
+                &settings.index_file_names,
+            );
+            match index_path {
+                Ok(Some(index_path)) => index_path,
+                // Folder without index file can be listed
+                Ok(None) if settings.autoindex => {
+                    return serve_listing(settings, request, &full_path)
+                }
+                Ok(None) => return response::Response::error_page(response::Status::NotFound),
+                Err(error) => {
+                    eprintln!("Error in HTTP connection: {error}");
+                    return response::Response::error_page(response::Status::Forbidden);
+                }
+            }
+        }
+        Ok(Some(full_path)) => full_path,
+        // The file does not exist
+        Ok(None) => return response::Response::error_page(response::Status::NotFound),
+        Err(error) => {
             eprintln!("Error in HTTP connection: {error}");
-            return;
+            return response::Response::error_page(response::Status::Forbidden);
         }
-        drop(buf_writer);
+    };
 
-        let result = stream.shutdown(net::Shutdown::Both);
-        if let Err(error) = result {
+    // Precompressed sibling ("app.js.br" for "app.js") is served instead of the file if the client accepts its encoding
+    let (precompressed, has_precompressed) = if settings.precompressed {
+        find_precompressed(settings, request, &full_path)
+    } else {
+        (None, false)
+    };
+    let file_path = precompressed.as_ref().map_or(&full_path, |(_, path)| path);
+
+    // Try to open requested file and form HTTP answer, the file content is streamed when the answer is written
+    match site_path::open_file(file_path) {
+        Ok(Some((file, metadata))) => {
+            // The precompressed file has the type of the original file
+            let content_type = settings.mime_types.get(&full_path);
+            let compressible =
+                precompressed.is_none() && is_compressible(settings, content_type, metadata.len());
+            let encoding = if compressible {
+                negotiate_encoding(request, metadata.len())
+            } else {
+                None
+            };
+            let validators = conditional::Validators::new(
+                &file,
+                file_path,
+                &metadata,
+                settings.entity_tag_kind,
+                &settings.content_hashes,
+            );
+            let mut validators = match validators {
+                Ok(validators) => validators,
+                Err(error) => {
+                    eprintln!("Error in HTTP connection: {}", Error::FileReadError(error));
+                    return response::Response::error_page(response::Status::InternalServerError);
+                }
+            };
+            // The compressed body is not byte-for-byte the file, so its entity tag is weak
+            if encoding.is_some() {
+                validators.entity_tag.weak = true;
+            }
+            let mut response = match conditional::evaluate(request, &validators) {
+                conditional::Precondition::Passed => {
+                    let mut response =
+                        serve_file_ranges(request, content_type, file, &metadata, &validators);
+                    // Ranges of the precompressed file are ranges of its encoded content
+                    if let Some((encoding, _)) = precompressed {
+                        if matches!(
+                            response.status(),
+                            response::Status::Ok | response::Status::PartialContent
+                        ) {
+                            response.add_header("Content-Encoding", encoding.as_str());
+                        }
+                    }
+                    match encoding {
+                        Some(encoding) => encode_response(response, encoding, request),
+                        None => response,
+                    }
+                }
+                conditional::Precondition::NotModified => {
+                    response::Response::new(response::Status::NotModified)
+                }
+                conditional::Precondition::Failed => {
+                    response::Response::error_page(response::Status::PreconditionFailed)
+                }
+            };
+            response.add_header("Accept-Ranges", "bytes");
+            // The body depends on the Accept-Encoding header
+            if compressible || has_precompressed {
+                response.add_header("Vary", "Accept-Encoding");
+            }
+            response.add_header("ETag", &validators.entity_tag.to_string());
+            if let Some(last_modified) = validators.last_modified {
+                response.add_header("Last-Modified", &last_modified.to_http_date());
+            }
+            // Caching headers describe the file, so the error pages do not get them
+            let cache_rule = full_path
+                .strip_prefix(&settings.root_folder_path)
+                .ok()
+                .and_then(|relative_path| settings.cache_rules.find(relative_path));
+            if let Some((cache_control, max_age)) = cache_rule {
+                if matches!(
+                    response.status(),
+                    response::Status::Ok
+                        | response::Status::PartialContent
+                        | response::Status::NotModified
+                ) {
+                    response.add_cache_control(cache_control, max_age);
+                }
+            }
+            response
+        }
+        Ok(None) => response::Response::error_page(response::Status::NotFound),
+        Err(error) => {
+            eprintln!("Error in HTTP connection: {}", Error::FileReadError(error));
+            response::Response::error_page(response::Status::InternalServerError)
+        }
+    }
+}
+
+/// Forms the answer containing the whole file or the ranges of the file requested by the Range header.
+/// Range is applied only to GET requests and only if the If-Range validator (if any) matches the file.
+fn serve_file_ranges(
+    request: &request::Request,
+    content_type: &str,
+    mut file: fs::File,
+    metadata: &fs::Metadata,
+    validators: &conditional::Validators,
+) -> response::Response {
+    let complete_length = metadata.len();
+    let ranges = match request.headers.get("Range") {
+        Some(value)
+            if request.method == request::Method::Get
+                && conditional::is_range_applicable(request, validators) =>
+        {
+            range::parse(value, complete_length)
+        }
+        _ => range::Ranges::Full,
+    };
+    match ranges {
+        range::Ranges::Full => {
+            response::Response::with_file(response::Status::Ok, content_type, file, complete_length)
+        }
+        range::Ranges::Satisfiable(ranges) if ranges.len() == 1 => {
+            let range = ranges[0];
+            if let Err(error) = file.seek(io::SeekFrom::Start(range.start)) {
+                eprintln!("Error in HTTP connection: {}", Error::FileReadError(error));
+                return response::Response::error_page(response::Status::InternalServerError);
+            }
+            let mut response = response::Response::with_file(
+                response::Status::PartialContent,
+                content_type,
+                file,
+                range.length,
+            );
+            response.add_header("Content-Range", &range.content_range(complete_length));
+            response
+        }
+        range::Ranges::Satisfiable(ranges) => {
+            response::Response::with_file_ranges(content_type, file, &ranges, complete_length)
+        }
+        range::Ranges::Unsatisfiable => {
+            let mut response =
+                response::Response::error_page(response::Status::RangeNotSatisfiable);
+            response.add_header("Content-Range", &format!("bytes */{complete_length}"));
+            response
+        }
+    }
+}
+
+/// Replaces the built-in error page by the custom page configured for the status.
+/// The built-in page is kept if there is no custom page for the status or its file is missing.
+fn replace_error_page(settings: &Settings, mut response: response::Response) -> response::Response {
+    // The redirects also have the built-in page, but their body is not replaced
+    if !response.is_error_page() || response.status().code() < 400 {
+        return response;
+    }
+    let Some(page_path) = settings.error_pages.get(&response.status().code()) else {
+        return response;
+    };
+    // The path is checked when the server is configured, it is absolute and has no ".." segments
+    let relative_path = path::Path::new(page_path.trim_start_matches('/'));
+    let full_path = match site_path::resolve(&settings.root_folder_path, relative_path) {
+        Ok(Some(full_path)) => full_path,
+        Ok(None) => return response,
+        Err(error) => {
             eprintln!("Error in HTTP connection: {error}");
-            return;
+            return response;
+        }
+    };
+    match site_path::open_file(&full_path) {
+        Ok(Some((file, metadata))) if metadata.is_file() => {
+            let content_type = settings.mime_types.get(&full_path);
+            response.set_file_body(content_type, file, metadata.len());
+        }
+        Ok(_) => {}
+        Err(error) => eprintln!("Error in HTTP connection: {}", Error::FileReadError(error)),
+    }
+    response
+}
+
+/// Forms the answer containing the folder listing
+fn serve_listing(
+    settings: &Settings,
+    request: &request::Request,
+    folder_path: &path::Path,
+) -> response::Response {
+    let listing = autoindex::Listing::read(
+        &settings.root_folder_path,
+        folder_path,
+        request,
+        settings.autoindex_show_hidden,
+    );
+    let listing = match listing {
+        Ok(listing) => listing,
+        Err(error) => {
+            eprintln!("Error in HTTP connection: {}", Error::FileReadError(error));
+            return response::Response::error_page(response::Status::InternalServerError);
+        }
+    };
+    let (content_type, body) = if autoindex::is_json_requested(request) {
+        ("application/json; charset=utf-8", listing.to_json())
+    } else {
+        ("text/html; charset=utf-8", listing.to_html())
+    };
+    let compressible = is_compressible(settings, content_type, body.len() as u64);
+    let encoding = if compressible {
+        negotiate_encoding(request, body.len() as u64)
+    } else {
+        None
+    };
+    let mut response =
+        response::Response::with_body(response::Status::Ok, content_type, body.into_bytes());
+    if let Some(encoding) = encoding {
+        response = encode_response(response, encoding, request);
+    }
+    // The listing format depends on the Accept header, its compression on the Accept-Encoding header
+    if compressible {
+        response.add_header("Vary", "Accept, Accept-Encoding");
+    } else {
+        response.add_header("Vary", "Accept");
+    }
+    response
+}
+
+/// Checks if the body of the content type and length should be compressed for the clients that accept compression
+fn is_compressible(settings: &Settings, content_type: &str, length: u64) -> bool {
+    settings.compression
+        && length >= settings.compression_min_size
+        && compression::is_compressible(content_type)
+}
+
+/// Chooses the encoding of the compressible body of the length, None if the body should be sent uncompressed
+fn negotiate_encoding(request: &request::Request, length: u64) -> Option<compression::Encoding> {
+    // Ranges are taken from the uncompressed file
+    if request.method == request::Method::Get && request.headers.contains("Range") {
+        return None;
+    }
+    // HTTP/1.0 clients do not support the chunked transfer coding, so the compressed body is buffered in memory
+    // and only small files are compressed for them
+    if request.version == request::Version::Http10 && length > MAX_BUFFERED_ENCODING_SIZE {
+        return None;
+    }
+    compression::negotiate(request, &compression::Encoding::ON_THE_FLY)
+}
+
+/// Finds the precompressed siblings of the file ("app.js.br", "app.js.zst" and "app.js.gz" for "app.js")
+/// and chooses the one whose encoding the client accepts.
+/// Returns the chosen sibling (if any) and whether the file has siblings at all.
+fn find_precompressed(
+    settings: &Settings,
+    request: &request::Request,
+    full_path: &path::Path,
+) -> (Option<(compression::Encoding, path::PathBuf)>, bool) {
+    let Ok(relative_path) = full_path.strip_prefix(&settings.root_folder_path) else {
+        return (None, false);
+    };
+    let mut siblings = Vec::new();
+    for encoding in compression::Encoding::PRECOMPRESSED {
+        let sibling_path = site_path::find_sibling(
+            &settings.root_folder_path,
+            relative_path,
+            encoding.file_extension(),
+        );
+        match sibling_path {
+            Ok(Some(sibling_path)) => siblings.push((encoding, sibling_path)),
+            Ok(None) => {}
+            // The sibling is skipped, the file itself can still be served
+            Err(error) => eprintln!("Error in HTTP connection: {error}"),
+        }
+    }
+    let encodings: Vec<compression::Encoding> =
+        siblings.iter().map(|(encoding, _)| *encoding).collect();
+    let has_siblings = !siblings.is_empty();
+    let chosen = compression::negotiate(request, &encodings).and_then(|chosen_encoding| {
+        siblings
+            .into_iter()
+            .find(|(encoding, _)| *encoding == chosen_encoding)
+    });
+    (chosen, has_siblings)
+}
+
+/// Compresses the response body, the file body is compressed while it is sent to HTTP/1.1 clients
+fn encode_response(
+    mut response: response::Response,
+    encoding: compression::Encoding,
+    request: &request::Request,
+) -> response::Response {
+    let chunked = request.version == request::Version::Http11;
+    match response.encode(encoding, chunked) {
+        Ok(()) => response,
+        Err(error) => {
+            eprintln!("Error in HTTP connection: {}", Error::FileReadError(error));
+            response::Response::error_page(response::Status::InternalServerError)
         }
     }
 }
 
-/// Reads the HTTP request, returns Ok(String) if it is an HTTP request, otherwise it returns an error.
-fn read_http_request(mut buf_reader: impl BufRead) -> Result<String, Error> {
-    // Need to find out if the request is an HTTP request.
-    // We are only interested in GET requests,
-    // so we need to make sure that the first 3 chars are "GET".
-    // "GET" in UTF-8 takes 3 bytes
-    let mut buf: [u8; 3] = [0; 3];
-    // Reading 3 bytes
-    let result = buf_reader.read_exact(&mut buf);
-    if let Err(error) = result {
-        return Err(Error::RequestReadError(error));
+/// Forms the answer redirecting from "/folder" to "/folder/", the query is preserved
+fn redirect_to_folder(request: &request::Request) -> response::Response {
+    let mut location = format!("{}/", request.path);
+    if !request.query.is_empty() {
+        location.push('?');
+        location.push_str(request.query.as_str());
     }
+    let mut response = response::Response::error_page(response::Status::MovedPermanently);
+    response.add_header("Location", &location);
+    response
+}
+
+/// Answers the request with the redirect to the same URL over HTTPS, the host is taken from the request
+fn redirect_to_https(
+    https_redirect: &HttpsRedirect,
+    request: &request::Request,
+) -> response::Response {
+    let Some(host_name) = request.host().and_then(host_name) else {
+        // The URL can not be formed without the host, HTTP/1.0 requests may have no Host field
+        return response::Response::error_page(response::Status::BadRequest);
+    };
+    let mut location = format!("https://{host_name}");
+    if https_redirect.https_port != 443 {
+        location.push_str(&format!(":{}", https_redirect.https_port));
+    }
+    // "OPTIONS *" and CONNECT requests have no path
+    if request.path.starts_with('/') {
+        location.push_str(&request.path);
+    } else {
+        location.push('/');
+    }
+    if !request.query.is_empty() {
+        location.push('?');
+        location.push_str(request.query.as_str());
+    }
+    let mut response = response::Response::error_page(https_redirect.status);
+    response.add_header("Location", &location);
+    response
+}
 
-    // Contains GET?
-    if buf != "GET".as_bytes() {
-        return Err(Error::WrongRequest);
+/// Extracts the host name from the host with optional port ("example.com:8080", "[::1]:8080").
+/// Returns None if the host name contains the characters that are not allowed in it.
+fn host_name(host: &str) -> Option<&str> {
+    // Brackets are allowed only around the IPv6 address
+    if let Some(rest) = host.strip_prefix('[') {
+        let address_length = rest.find(']')?;
+        let address = &rest[..address_length];
+        let is_allowed = |byte: u8| byte.is_ascii_hexdigit() || matches!(byte, b':' | b'.');
+        return (!address.is_empty() && address.bytes().all(is_allowed))
+            .then_some(&host[..address_length + 2]);
     }
+    let host_name = host.split(':').next()?;
+    let is_allowed = |byte: u8| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_');
+    (!host_name.is_empty() && host_name.bytes().all(is_allowed)).then_some(host_name)
+}
+
+/// Writes HTTP answer to the stream
+fn write_http_answer<S: stream::ConnectionStream>(
+    settings: &Settings,
+    stream: &S,
+    response: &response::Response,
+    include_body: bool,
+) -> Result<(), Error>
+where
+    for<'a> &'a S: io::Read + io::Write,
+{
+    // Write HTTP answer
+    stream
+        .write_response(response, include_body, settings.use_sendfile)
+        .map_err(Error::AnswerWriteError)
+}
 
-    // This is a GET request.
-    // Try to read him
+/// Reads the HTTP request head (request line and headers), returns Ok(Some(String)) if it is an HTTP request, otherwise it returns an error.
+/// Returns Ok(None) if the connection was closed before the request started.
+fn read_http_request(buf_reader: impl BufRead) -> Result<Option<String>, Error> {
     // Since there is a possibility that this request is formed incorrectly and has no end,
     // we must limit the number of bytes to be read.
     let mut request = String::with_capacity(MAX_REQUEST_READ_SIZE);
-    request.push_str("GET");
     // Take guarantees that we will not be able to read more than MAX_REQUEST_READ_SIZE bytes,
     // it will always return EOF
     let mut take = buf_reader.take(MAX_REQUEST_READ_SIZE as u64);
@@ -133,141 +646,263 @@ fn read_http_request(mut buf_reader: impl BufRead) -> Result<String, Error> {
     loop {
         // Read line from stream to the string
         let result = take.read_line(&mut line);
-        match result {
-            Err(error) => return Err(Error::RequestReadError(error)),
-            // EOF reached, request is wrong or too large (> MAX_REQUEST_READ_SIZE + "GET".len())
-            Ok(0) => {
-                return Err(Error::WrongRequest);
-            }
-            Ok(_) => {
-                // Final line of the HTTP request is empty
-                request += line.as_str();
-                if line == "\r\n" || line == "\n" {
-                    break;
+        if let Err(error) = result {
+            return Err(Error::RequestReadError(error));
+        }
+        // Line without the end means that EOF is reached
+        if !line.ends_with('\n') {
+            // Limit reached, request is too large (> MAX_REQUEST_READ_SIZE)
+            if take.limit() == 0 {
+                // The request line has not been read completely
+                if request.is_empty() {
+                    return Err(Error::UriTooLong);
                 }
-                line.clear();
+                return Err(Error::HeadersTooLarge);
             }
-        }
-    }
-    Ok(request)
-}
-
-fn get_requested_path(request: &String) -> Result<path::PathBuf, Error> {
-    let first_line = request.lines().next().unwrap();
-    // First line is "GET PATH HTTP..."
-    // It is necessary to find the PATH
-    let path_string: String = first_line
-        .chars()
-        .skip_while(|&ch| ch != ' ') // Skips first word
-        .skip(1) // Skips space before PATH
-        .take_while(|&ch| ch != ' ') // Takes PATH until space before HTTP...
-        .collect();
-    // Decode URI string from "percent-encoding"
-    let path_string = urlencoding::decode(path_string.as_str())?;
-    Ok(path_string.to_string().into())
-}
-
-/// Tries to get the required file, returns None if it failed to do so.
-// In a good way, I should have moved the actions related to reading server files to a separate module, but right now there is too little code.
-fn get_file_content(path: &path::Path) -> Option<String> {
-    match path.try_exists() {
-        Ok(is_exist) => {
-            if !is_exist {
-                return None;
+            // Connection closed without a new request
+            if request.is_empty() && line.is_empty() {
+                return Ok(None);
             }
+            // Connection closed in the middle of the request, request is wrong
+            return Err(Error::WrongRequest);
         }
-        Err(_error) => {
-            return None;
-        }
-    }
-    // Read requested file
-    let result = fs::read_to_string(path);
-    result.ok()
-}
-
-/// Forms HTTP answer
-/// If the requested file was unavailable, then requested_file_content should be None
-fn form_http_answer(requested_file_content: Option<&String>) -> String {
-    let mut answer = String::new();
-    // Adds first line
-    match requested_file_content {
-        None => {
-            answer.push_str("HTTP/1.1 404 Not Found\r\n");
-        }
-        Some(_content) => {
-            answer.push_str("HTTP/1.1 200 OK\r\n");
+        // Empty lines before the request line should be ignored (RFC 9112, section 2.2)
+        if request.is_empty() && (line == "\r\n" || line == "\n") {
+            line.clear();
+            continue;
         }
-    }
-    // Adds Server header
-    answer.push_str("Server: Simple Web Server\r\n");
-    // Adds Connection header
-    answer.push_str("Connection: close\r\n");
-    // Adds Content-Type header
-    answer.push_str("Content-Type: text/html\r\n");
-    // Select content
-    let content: &str;
-    match requested_file_content {
-        None => {
-            content = NOT_FOUND_HTML_PAGE_CODE;
+        // Final line of the HTTP request is empty
+        request += line.as_str();
+        if line == "\r\n" || line == "\n" {
+            break;
         }
-        Some(requested_file_content) => content = requested_file_content.as_str(),
+        line.clear();
     }
-    // Adds Content-Length header
-    use std::fmt::Write;
-    let _ = write!(&mut answer, "Content-Length: {}\r\n", content.len());
-    // Adds empty line
-    answer.push_str("\r\n");
-    // Adds content
-    answer.push_str(content);
-    answer
+    Ok(Some(request))
 }
 
 #[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     #[error("Failed to read request from socket: {0}")]
     RequestReadError(io::Error),
     #[error("Wrong request")]
     WrongRequest,
-    #[error("Wrong URI in request {0}")]
-    WrongUri(#[from] string::FromUtf8Error),
+    #[error("Request line is too long")]
+    UriTooLong,
+    #[error("Request header fields are too large")]
+    HeadersTooLarge,
+    #[error("Wrong request: {0}")]
+    WrongRequestHead(#[from] request::Error),
+    #[error("Failed to read requested file {0}")]
+    FileReadError(io::Error),
     #[error("Failed to write HTTP answer to socket {0}")]
-    _AnswerWriteError(io::Error),
+    AnswerWriteError(io::Error),
     #[error("Failed to shutdown TCP connection {0}")]
-    _ShutdownFailed(io::Error),
+    ShutdownFailed(io::Error),
+    #[error("TLS handshake failed: {0}")]
+    TlsHandshakeError(io::Error),
 }
 
-static NOT_FOUND_HTML_PAGE_CODE: &str = r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <title>Simple Web Server</title>
-    <style>
-        body {
-            font-family: Arial, sans-serif;
-            background-color: #f0f0f0;
-            margin: 0;
-            padding: 0;
-            display: flex;
-            justify-content: center;
-            align-items: center;
-            height: 100vh;
-            flex-direction: column;
-        }
-        h1 {
-            color: #333;
+impl Error {
+    /// Returns the status of the HTTP answer that informs the client about the error
+    fn status(&self) -> response::Status {
+        match self {
+            // Request is not a valid UTF-8 text
+            Self::RequestReadError(error) if error.kind() == io::ErrorKind::InvalidData => {
+                response::Status::BadRequest
+            }
+            Self::WrongRequest => response::Status::BadRequest,
+            Self::UriTooLong => response::Status::UriTooLong,
+            Self::HeadersTooLarge => response::Status::RequestHeaderFieldsTooLarge,
+            Self::WrongRequestHead(request::Error::UnknownMethod(_)) => {
+                response::Status::NotImplemented
+            }
+            Self::WrongRequestHead(request::Error::UnsupportedVersion(_)) => {
+                response::Status::HttpVersionNotSupported
+            }
+            Self::WrongRequestHead(_) => response::Status::BadRequest,
+            Self::RequestReadError(_)
+            | Self::FileReadError(_)
+            | Self::AnswerWriteError(_)
+            | Self::ShutdownFailed(_)
+            | Self::TlsHandshakeError(_) => response::Status::InternalServerError,
         }
-        p {
-            color: #666;
-            text-align: center;
-            max-width: 600px;
-            margin: 0;
-            font-size: 1.2em;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_pipelined_requests() {
+        let mut buf_reader = io::BufReader::new(
+            "GET /a HTTP/1.1\r\nHost: a\r\n\r\nGET /b HTTP/1.1\r\nHost: a\r\n\r\n".as_bytes(),
+        );
+        let first = read_http_request(&mut buf_reader).unwrap().unwrap();
+        assert_eq!(first, "GET /a HTTP/1.1\r\nHost: a\r\n\r\n");
+        let second = read_http_request(&mut buf_reader).unwrap().unwrap();
+        assert_eq!(second, "GET /b HTTP/1.1\r\nHost: a\r\n\r\n");
+        // Connection is closed after the last request
+        assert!(matches!(read_http_request(&mut buf_reader), Ok(None)));
+    }
+
+    #[test]
+    fn read_truncated_request() {
+        let buf_reader = io::BufReader::new("GET / HTTP/1.1\r\nHost: a\r\n".as_bytes());
+        assert!(matches!(
+            read_http_request(buf_reader),
+            Err(Error::WrongRequest)
+        ));
+    }
+
+    #[test]
+    fn read_too_large_requests() {
+        let long_line = format!(
+            "GET /{} HTTP/1.1\r\n\r\n",
+            "a".repeat(MAX_REQUEST_READ_SIZE)
+        );
+        let result = read_http_request(io::BufReader::new(long_line.as_bytes()));
+        assert!(matches!(result, Err(Error::UriTooLong)));
+
+        let large_headers = format!(
+            "GET / HTTP/1.1\r\nHost: a\r\nCookie: {}\r\n\r\n",
+            "a".repeat(MAX_REQUEST_READ_SIZE)
+        );
+        let result = read_http_request(io::BufReader::new(large_headers.as_bytes()));
+        assert!(matches!(result, Err(Error::HeadersTooLarge)));
+    }
+
+    #[test]
+    fn https_redirect_location() {
+        let https_redirect = HttpsRedirect {
+            status: response::Status::PermanentRedirect,
+            https_port: 8443,
+        };
+        let location = |head: &str| {
+            let response = redirect_to_https(&https_redirect, &request::parse(head).unwrap());
+            let mut bytes = Vec::new();
+            response.write_to(&mut bytes, false).unwrap();
+            let head = String::from_utf8(bytes).unwrap();
+            let location = head
+                .lines()
+                .find_map(|line| line.strip_prefix("Location: "))
+                .map(str::to_string);
+            (response.status().code(), location)
+        };
+        assert_eq!(
+            location("POST /a%20b/c.html?x=1&y=2 HTTP/1.1\r\nHost: example.com:8080\r\n\r\n"),
+            (
+                308,
+                Some("https://example.com:8443/a%20b/c.html?x=1&y=2".to_string())
+            )
+        );
+        assert_eq!(
+            location("GET / HTTP/1.1\r\nHost: [::1]:8080\r\n\r\n"),
+            (308, Some("https://[::1]:8443/".to_string()))
+        );
+        assert_eq!(
+            location("OPTIONS * HTTP/1.1\r\nHost: example.com\r\n\r\n"),
+            (308, Some("https://example.com:8443/".to_string()))
+        );
+        assert_eq!(location("GET / HTTP/1.0\r\n\r\n"), (400, None));
+        for host in ["evil.com/path", "a]b[", "a[b]", "[]", "[::1", "[evil.com]"] {
+            assert_eq!(
+                location(&format!("GET / HTTP/1.1\r\nHost: {host}\r\n\r\n")),
+                (400, None),
+                "{host}"
+            );
         }
-    </style>
-</head>
-<body>
-    <h1>404</h1>
-    <p>Page Not Found</p>
-</body>
-</html>
-"#;
+
+        let https_redirect = HttpsRedirect {
+            status: response::Status::MovedPermanently,
+            https_port: 443,
+        };
+        let response = redirect_to_https(
+            &https_redirect,
+            &request::parse("GET /a HTTP/1.1\r\nHost: Example.com\r\n\r\n").unwrap(),
+        );
+        let mut bytes = Vec::new();
+        response.write_to(&mut bytes, false).unwrap();
+        let head = String::from_utf8(bytes).unwrap();
+        assert!(head.starts_with("HTTP/1.1 301 Moved Permanently\r\n"));
+        assert!(head.contains("\r\nLocation: https://Example.com/a\r\n"));
+    }
+
+    #[test]
+    fn custom_error_pages() {
+        let settings = Settings {
+            root_folder_path: path::Path::new("./www").canonicalize().unwrap(),
+            mime_types: mime::MimeTypes::new("application/octet-stream"),
+            index_file_names: Vec::new(),
+            autoindex: false,
+            autoindex_show_hidden: false,
+            keep_alive_timeout: time::Duration::ZERO,
+            max_requests_per_connection: 1,
+            use_sendfile: false,
+            entity_tag_kind: config::EntityTagKind::Strong,
+            content_hashes: conditional::ContentHashes::default(),
+            compression: false,
+            compression_min_size: 0,
+            precompressed: false,
+            cache_rules: cache_control::CacheRules::new(&[]),
+            error_pages: collections::HashMap::from([
+                (301, "/index.html".to_string()),
+                (404, "/index.html".to_string()),
+            ]),
+            https_redirect: None,
+            strict_transport_security: None,
+        };
+        let response = replace_error_page(
+            &settings,
+            response::Response::error_page(response::Status::NotFound),
+        );
+        assert!(!response.is_error_page());
+
+        // The redirect keeps its built-in body
+        let request = request::parse("GET /folder HTTP/1.1\r\nHost: a\r\n\r\n").unwrap();
+        let response = replace_error_page(&settings, redirect_to_folder(&request));
+        assert_eq!(response.status().code(), 301);
+        assert!(response.is_error_page());
+    }
+
+    #[test]
+    fn error_statuses() {
+        let status = |error: Error| error.status().code();
+        assert_eq!(status(Error::WrongRequest), 400);
+        assert_eq!(status(Error::UriTooLong), 414);
+        assert_eq!(status(Error::HeadersTooLarge), 431);
+        assert_eq!(status(request::Error::WrongRequestLine.into()), 400);
+        assert_eq!(status(request::Error::WrongHost.into()), 400);
+        assert_eq!(
+            status(request::Error::UnknownMethod("BREW".to_string()).into()),
+            501
+        );
+        assert_eq!(
+            status(request::Error::UnsupportedVersion("HTTP/2.0".to_string()).into()),
+            505
+        );
+        let invalid_data = io::Error::new(io::ErrorKind::InvalidData, "not UTF-8");
+        assert_eq!(status(Error::RequestReadError(invalid_data)), 400);
+        let other = io::Error::other("other");
+        assert_eq!(status(Error::FileReadError(other)), 500);
+    }
+
+    #[test]
+    fn keep_alive_defaults() {
+        let keep_alive = |head: &str| is_keep_alive(&request::parse(head).unwrap());
+        assert!(keep_alive("GET / HTTP/1.1\r\nHost: a\r\n\r\n"));
+        assert!(!keep_alive(
+            "GET / HTTP/1.1\r\nHost: a\r\nConnection: Close\r\n\r\n"
+        ));
+        assert!(!keep_alive("GET / HTTP/1.0\r\n\r\n"));
+        assert!(keep_alive(
+            "GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n"
+        ));
+        // The body is not read, so the connection can not be reused
+        assert!(!keep_alive(
+            "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\n\r\n"
+        ));
+    }
+}
@@ -1,30 +1,244 @@
 /// Server
-use std::{io, net, sync, sync::mpsc};
+use std::{io, net, path, sync, sync::mpsc, time};
 
+#[cfg(unix)]
+use std::{io::Read as _, io::Write as _, os::fd::AsFd, os::unix::net as unix_net};
+
+mod autoindex;
+mod cache_control;
+mod compression;
+mod conditional;
 pub mod config;
+mod date_time;
 mod http_connection;
+mod listener;
+pub mod mime;
+mod poll;
+mod range;
+pub mod request;
+mod response;
+#[cfg(target_os = "linux")]
+mod sendfile;
+mod site_path;
+mod stream;
+#[cfg(target_os = "linux")]
+mod systemd;
 mod thread_pool;
+pub mod tls;
 
-pub struct Server<'a> {
-    config: config::Config<'a>,
-    tcp_listener: net::TcpListener,
+pub struct Server {
+    /// All listeners are served by the same thread pool and are closed together
+    listeners: Vec<Listener>,
     thread_pool: thread_pool::ThreadPool,
 
     ctrl_c_receiver: mpsc::Receiver<()>,
+    /// The Ctrl-C handler writes to the pair of this socket to wake up the accept loop waiting in poll(2)
+    #[cfg(unix)]
+    wake_receiver: unix_net::UnixStream,
+    /// Sender of the service state notifications, if the server is started by systemd with Type=notify
+    #[cfg(target_os = "linux")]
+    notifier: Option<systemd::Notifier>,
+}
+
+/// Bound listener with the settings of its connections
+struct Listener {
+    socket: ListenerSocket,
+    connection_settings: sync::Arc<http_connection::Settings>,
+    /// TLS configuration, None for plain HTTP
+    tls_config: Option<sync::Arc<rustls::ServerConfig>>,
+}
+
+enum ListenerSocket {
+    Tcp(net::TcpListener),
+    #[cfg(unix)]
+    Unix(listener::UnixSocketListener),
 }
 
-impl<'a> Server<'a> {
+impl ListenerSocket {
+    /// The connections are accepted only when poll(2) reports them, but they may be gone by that time
+    fn set_nonblocking(&self) -> Result<(), io::Error> {
+        match self {
+            Self::Tcp(tcp_listener) => tcp_listener.set_nonblocking(true),
+            #[cfg(unix)]
+            Self::Unix(unix_listener) => unix_listener.listener().set_nonblocking(true),
+        }
+    }
+
+    #[cfg(unix)]
+    fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+        match self {
+            Self::Tcp(tcp_listener) => tcp_listener.as_fd(),
+            Self::Unix(unix_listener) => unix_listener.listener().as_fd(),
+        }
+    }
+
+    /// Address of the TCP socket, None for the Unix socket
+    fn local_addr(&self) -> Option<net::SocketAddr> {
+        match self {
+            Self::Tcp(tcp_listener) => tcp_listener.local_addr().ok(),
+            #[cfg(unix)]
+            Self::Unix(_) => None,
+        }
+    }
+}
+
+impl Server {
     /// Creates and initializes the server
-    pub fn init(config: config::Config<'a>) -> Result<Self, Error> {
-        // Binding TCP listener
-        let tcp_listener = net::TcpListener::bind(config.socket_addr_v4)?;
+    pub fn init(config: config::Config) -> Result<Self, Error> {
+        // Loading MIME types
+        let mut mime_types = mime::MimeTypes::new(config.default_mime_type);
+        if let Some(mime_types_file_path) = config.mime_types_file_path {
+            mime_types.load_file(mime_types_file_path)?;
+        }
+
+        // Sockets passed by systemd are used instead of binding the addresses
+        let mut activated_sockets = if config.socket_activation {
+            Some(take_activated_sockets()?)
+        } else {
+            None
+        };
+
+        // TLS configurations are created with the first listener that uses them
+        let mut tls_configs = tls::ServerConfigs::default();
+
+        // Binding TCP listeners
+        let mut listeners = Vec::with_capacity(config.listeners.len() + 2);
+        for (index, listener_config) in config.listeners.iter().enumerate() {
+            let socket = tcp_listener_socket(
+                &mut activated_sockets,
+                listener_config.socket_addr,
+                config.dual_stack,
+            )?;
+            let Some(socket) = socket else {
+                // The main listener has the common settings, so nothing is lost if the passed sockets are bound elsewhere.
+                // The other listeners have their own root folder or TLS settings, they are not served by the common ones.
+                if index == 0 {
+                    eprintln!(
+                        "No socket is passed for {}, it is not listened on.",
+                        listener_config.socket_addr
+                    );
+                    continue;
+                }
+                return Err(Error::MissingActivatedSocketError(
+                    listener_config.socket_addr.to_string(),
+                ));
+            };
+            listeners.push(new_listener(
+                &config,
+                &mime_types,
+                &mut tls_configs,
+                socket,
+                listener_config.root_folder_path,
+                listener_config.tls_certificates.as_deref(),
+                None,
+            )?);
+        }
+        let https_redirect_socket = match config.https_redirect {
+            Some(https_redirect) => Some(
+                tcp_listener_socket(
+                    &mut activated_sockets,
+                    https_redirect.socket_addr,
+                    config.dual_stack,
+                )?
+                .ok_or_else(|| {
+                    Error::MissingActivatedSocketError(https_redirect.socket_addr.to_string())
+                })?,
+            ),
+            None => None,
+        };
+        let unix_socket = match config.unix_socket {
+            Some(unix_socket) => Some(
+                unix_listener_socket(&mut activated_sockets, unix_socket)?.ok_or_else(|| {
+                    Error::MissingActivatedSocketError(unix_socket.path.display().to_string())
+                })?,
+            ),
+            None => None,
+        };
+        // The passed sockets that are not bound to the configured addresses get the common settings
+        for socket in activated_sockets.unwrap_or_default() {
+            // Unix sockets serve the local reverse proxy that terminates TLS
+            let tls_certificates = match socket {
+                ListenerSocket::Tcp(_) => None,
+                #[cfg(unix)]
+                ListenerSocket::Unix(_) => Some(&[][..]),
+            };
+            listeners.push(new_listener(
+                &config,
+                &mime_types,
+                &mut tls_configs,
+                socket,
+                None,
+                tls_certificates,
+                None,
+            )?);
+        }
+        if let Some(unix_socket) = unix_socket {
+            // The reverse proxy terminates TLS, so plain HTTP is served from the common root folder
+            listeners.push(new_listener(
+                &config,
+                &mime_types,
+                &mut tls_configs,
+                unix_socket,
+                None,
+                Some(&[]),
+                None,
+            )?);
+        }
+        if let (Some(https_redirect), Some(socket)) = (config.https_redirect, https_redirect_socket)
+        {
+            // Requests are redirected to the first HTTPS listener
+            let https_port = listeners
+                .iter()
+                .filter(|listener| listener.tls_config.is_some())
+                .find_map(|listener| listener.socket.local_addr())
+                .map_or(443, |socket_addr| socket_addr.port());
+            let https_redirect = http_connection::HttpsRedirect {
+                status: if https_redirect.status_code == 308 {
+                    response::Status::PermanentRedirect
+                } else {
+                    response::Status::MovedPermanently
+                },
+                https_port,
+            };
+            listeners.push(new_listener(
+                &config,
+                &mime_types,
+                &mut tls_configs,
+                socket,
+                None,
+                Some(&[]),
+                Some(https_redirect),
+            )?);
+        }
+        #[cfg(target_os = "linux")]
+        let notifier = systemd::Notifier::from_env().map_err(Error::NotifySocketError)?;
+
+        for listener in &listeners {
+            listener.socket.set_nonblocking()?;
+        }
 
         // Without this, when receiving Ctrl-C, the program will immediately shut down, without correctly terminating the threads
         let (ctrl_c_sender, ctrl_c_receiver) = mpsc::channel::<()>();
+        // Self-pipe: the handler writes to one end, the accept loop waits for the other one together with the listeners
+        #[cfg(unix)]
+        let (wake_sender, wake_receiver) =
+            unix_net::UnixStream::pair().map_err(Error::WakePipeError)?;
+        #[cfg(unix)]
+        {
+            wake_sender
+                .set_nonblocking(true)
+                .map_err(Error::WakePipeError)?;
+            wake_receiver
+                .set_nonblocking(true)
+                .map_err(Error::WakePipeError)?;
+        }
         ctrlc::set_handler(move || {
             ctrl_c_sender
                 .send(())
                 .expect("Could not send CTRL-C signal on channel.");
+            // The byte may be dropped only if the socket buffer is full, then the loop is woken up already
+            #[cfg(unix)]
+            let _ = (&wake_sender).write(&[0]);
         })
         .expect("Error setting Ctrl-C handler");
 
@@ -32,43 +246,59 @@ impl<'a> Server<'a> {
         let thread_pool = thread_pool::ThreadPool::new(config.threads_number);
 
         Ok(Server {
-            config,
-            tcp_listener,
+            listeners,
             thread_pool,
             ctrl_c_receiver,
+            #[cfg(unix)]
+            wake_receiver,
+            #[cfg(target_os = "linux")]
+            notifier,
         })
     }
 
-    /// Handles incoming connections in loop
+    /// Handles incoming connections in loop.
+    /// The loop blocks until there are incoming connections, the watchdog notification is due or Ctrl-C is received.
     pub fn run(&self) {
-        let root_folder_path = sync::Arc::new(self.config.root_folder_path.to_owned());
+        #[cfg(unix)]
+        let mut poll = poll::Poll::new(
+            self.listeners
+                .iter()
+                .map(|listener| listener.socket.as_fd())
+                .chain([self.wake_receiver.as_fd()]),
+        );
+        #[cfg(not(unix))]
+        let mut poll = poll::Poll::new(self.listeners.len());
+
+        self.notify("READY=1");
+        let mut last_watchdog_notification = time::Instant::now();
         loop {
-            // Service incoming connections
-            let result = self.tcp_listener.set_nonblocking(true);
-            if result.is_err() {
-                return;
-            }
-            // Try to accept connection
-            let stream = self.tcp_listener.accept();
-            if let Ok((stream, _)) = stream {
-                let result = stream.set_nonblocking(false);
-                if result.is_err() {
-                    continue;
+            // Waiting for incoming connections, but not longer than until the next watchdog notification
+            let timeout = self
+                .watchdog_interval()
+                .map(|interval| interval.saturating_sub(last_watchdog_notification.elapsed()));
+            let ready_sockets = match poll.wait(timeout) {
+                Ok(ready_sockets) => ready_sockets,
+                Err(error) => {
+                    eprintln!("Error waiting for connections: {error}");
+                    break;
                 }
-                let peer_addr = stream.peer_addr();
-                match peer_addr {
-                    Ok(addr) => println!("Performing connection from {addr}..."),
-                    Err(_error) => eprintln!("Performing connection..."),
+            };
+            // Service incoming connections
+            for index in ready_sockets {
+                match self.listeners.get(index) {
+                    Some(listener) => self.accept_connection(listener),
+                    // The wake-up socket, Ctrl-C is checked below
+                    None => self.clear_wake_up(),
                 }
+            }
 
-                // Performs connection serving using the Thread Pool
-                let root_folder_path = sync::Arc::clone(&root_folder_path);
-                let job = Box::new(move || {
-                    let http_connection =
-                        http_connection::HTTPConnection::new(stream, root_folder_path);
-                    http_connection.perform();
-                });
-                self.thread_pool.send_job(job);
+            // systemd restarts the server if the notifications stop
+            let watchdog_interval = self.watchdog_interval();
+            if watchdog_interval
+                .is_some_and(|interval| last_watchdog_notification.elapsed() >= interval)
+            {
+                self.notify("WATCHDOG=1");
+                last_watchdog_notification = time::Instant::now();
             }
 
             // Ctrl-C handling
@@ -79,16 +309,289 @@ impl<'a> Server<'a> {
                 }
                 Ok(_) => {
                     // Ctrl-C is received, shutting down the server.
-                    return;
+                    break;
                 }
                 Err(mpsc::TryRecvError::Empty) => {}
             }
         }
+        self.notify("STOPPING=1");
+    }
+
+    /// Reads the bytes written by the Ctrl-C handler, so poll(2) does not report the wake-up socket again
+    fn clear_wake_up(&self) {
+        #[cfg(unix)]
+        while matches!((&self.wake_receiver).read(&mut [0; 16]), Ok(1..)) {}
+    }
+
+    /// Sends the service state to systemd, if the server is started with Type=notify
+    fn notify(&self, state: &str) {
+        #[cfg(target_os = "linux")]
+        if let Some(notifier) = &self.notifier {
+            if let Err(error) = notifier.notify(state) {
+                eprintln!("systemd notification error: {error}");
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = state;
+    }
+
+    /// How often WATCHDOG=1 is sent, None if the systemd watchdog is off
+    fn watchdog_interval(&self) -> Option<time::Duration> {
+        #[cfg(target_os = "linux")]
+        return self
+            .notifier
+            .as_ref()
+            .and_then(systemd::Notifier::watchdog_interval);
+        #[cfg(not(target_os = "linux"))]
+        None
+    }
+
+    /// Accepts the connection if there is one and sends it to the thread pool
+    fn accept_connection(&self, listener: &Listener) {
+        match &listener.socket {
+            ListenerSocket::Tcp(tcp_listener) => self.accept_tcp_connection(listener, tcp_listener),
+            #[cfg(unix)]
+            ListenerSocket::Unix(unix_listener) => {
+                self.accept_unix_connection(listener, unix_listener)
+            }
+        }
+    }
+
+    fn accept_tcp_connection(&self, listener: &Listener, tcp_listener: &net::TcpListener) {
+        // Try to accept connection
+        let Ok((stream, _)) = tcp_listener.accept() else {
+            return;
+        };
+        if stream.set_nonblocking(false).is_err() {
+            return;
+        }
+        let peer_addr = stream.peer_addr();
+        match peer_addr {
+            Ok(addr) => println!(
+                "Performing connection from {}...",
+                listener::display_peer_addr(addr)
+            ),
+            Err(_error) => eprintln!("Performing connection..."),
+        }
+
+        // Performs connection serving using the Thread Pool
+        let connection_settings = sync::Arc::clone(&listener.connection_settings);
+        let tls_config = listener.tls_config.clone();
+        let job = Box::new(move || match tls_config {
+            Some(tls_config) => match tls::TlsStream::new(tls_config, stream) {
+                Ok(tls_stream) => {
+                    http_connection::HTTPConnection::new(tls_stream, connection_settings).perform()
+                }
+                Err(error) => eprintln!("Error in HTTP connection: {error}"),
+            },
+            None => http_connection::HTTPConnection::new(stream, connection_settings).perform(),
+        });
+        self.thread_pool.send_job(job);
+    }
+
+    /// Connections of the Unix socket come from the local reverse proxy, they are always plain HTTP
+    #[cfg(unix)]
+    fn accept_unix_connection(
+        &self,
+        listener: &Listener,
+        unix_listener: &listener::UnixSocketListener,
+    ) {
+        // Try to accept connection
+        let Ok((stream, _)) = unix_listener.listener().accept() else {
+            return;
+        };
+        if stream.set_nonblocking(false).is_err() {
+            return;
+        }
+        // The peers of the Unix socket are unnamed
+        println!(
+            "Performing connection from {}...",
+            unix_listener.path().display()
+        );
+
+        // Performs connection serving using the Thread Pool
+        let connection_settings = sync::Arc::clone(&listener.connection_settings);
+        let job = Box::new(move || {
+            http_connection::HTTPConnection::new(stream, connection_settings).perform();
+        });
+        self.thread_pool.send_job(job);
+    }
+}
+
+/// Creates the listener serving the socket.
+/// The root folder and the TLS certificates that are None are taken from the common config, empty certificates mean plain HTTP.
+/// The listeners with the same certificates share the TLS configuration.
+fn new_listener<'a>(
+    config: &'a config::Config,
+    mime_types: &mime::MimeTypes,
+    tls_configs: &mut tls::ServerConfigs<'a>,
+    socket: ListenerSocket,
+    root_folder_path: Option<&path::Path>,
+    tls_certificates: Option<&'a [config::TlsCertificate<'a>]>,
+    https_redirect: Option<http_connection::HttpsRedirect>,
+) -> Result<Listener, Error> {
+    // Requested files are checked against the canonical root folder path
+    let root_folder_path = root_folder_path
+        .unwrap_or(config.root_folder_path)
+        .canonicalize()
+        .map_err(Error::RootFolderCanonicalizationError)?;
+    // Loading TLS certificates
+    let tls_certificates = tls_certificates.unwrap_or(&config.tls_certificates);
+    let tls_config = if tls_certificates.is_empty() {
+        None
+    } else {
+        Some(tls_configs.get(tls_certificates)?)
+    };
+    let connection_settings = http_connection::Settings {
+        https_redirect,
+        ..create_connection_settings(
+            config,
+            root_folder_path,
+            mime_types.clone(),
+            tls_config.is_some(),
+        )
+    };
+    Ok(Listener {
+        socket,
+        connection_settings: sync::Arc::new(connection_settings),
+        tls_config,
+    })
+}
+
+/// Binds the TCP listener to the address, or takes the passed socket bound to it if socket activation is on.
+/// Returns None if no passed socket is bound to the address.
+fn tcp_listener_socket(
+    activated_sockets: &mut Option<Vec<ListenerSocket>>,
+    socket_addr: net::SocketAddr,
+    dual_stack: bool,
+) -> Result<Option<ListenerSocket>, Error> {
+    let Some(activated_sockets) = activated_sockets else {
+        let tcp_listener = listener::bind_tcp(socket_addr, dual_stack)?;
+        return Ok(Some(ListenerSocket::Tcp(tcp_listener)));
+    };
+    let index = activated_sockets
+        .iter()
+        .position(|socket| socket.local_addr() == Some(socket_addr));
+    Ok(index.map(|index| activated_sockets.remove(index)))
+}
+
+/// Binds the Unix socket listener, or takes the passed socket with the same path if socket activation is on.
+/// Returns None if no passed socket has the path.
+fn unix_listener_socket(
+    activated_sockets: &mut Option<Vec<ListenerSocket>>,
+    unix_socket: config::UnixSocket,
+) -> Result<Option<ListenerSocket>, Error> {
+    #[cfg(unix)]
+    {
+        let Some(activated_sockets) = activated_sockets else {
+            let unix_listener = listener::bind_unix(unix_socket.path, unix_socket.mode)
+                .map_err(Error::UnixSocketBindingError)?;
+            return Ok(Some(ListenerSocket::Unix(unix_listener)));
+        };
+        let index = activated_sockets.iter().position(|socket| match socket {
+            ListenerSocket::Unix(unix_listener) => unix_listener.path() == unix_socket.path,
+            ListenerSocket::Tcp(_) => false,
+        });
+        Ok(index.map(|index| activated_sockets.remove(index)))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (activated_sockets, unix_socket);
+        Err(Error::UnixSocketBindingError(
+            io::ErrorKind::Unsupported.into(),
+        ))
+    }
+}
+
+/// Takes the listening sockets passed by systemd
+#[cfg(target_os = "linux")]
+fn take_activated_sockets() -> Result<Vec<ListenerSocket>, Error> {
+    let sockets = systemd::listen_sockets().map_err(Error::SocketActivationError)?;
+    Ok(sockets
+        .into_iter()
+        .map(|socket| match socket {
+            systemd::ActivatedSocket::Tcp(tcp_listener) => ListenerSocket::Tcp(tcp_listener),
+            systemd::ActivatedSocket::Unix(unix_listener) => {
+                ListenerSocket::Unix(listener::UnixSocketListener::adopt(unix_listener))
+            }
+        })
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn take_activated_sockets() -> Result<Vec<ListenerSocket>, Error> {
+    Err(Error::SocketActivationError(
+        io::ErrorKind::Unsupported.into(),
+    ))
+}
+
+/// Creates the settings of the HTTP connections, they are shared by the connections of one listener
+fn create_connection_settings(
+    config: &config::Config,
+    root_folder_path: path::PathBuf,
+    mime_types: mime::MimeTypes,
+    tls: bool,
+) -> http_connection::Settings {
+    http_connection::Settings {
+        root_folder_path,
+        mime_types,
+        index_file_names: config
+            .index_file_names
+            .iter()
+            .map(|index_file_name| index_file_name.to_string())
+            .collect(),
+        autoindex: config.autoindex,
+        autoindex_show_hidden: config.autoindex_show_hidden,
+        keep_alive_timeout: config.keep_alive_timeout,
+        // Zero timeout or zero requests number disables keep-alive, so only one request per connection is served
+        max_requests_per_connection: if config.keep_alive_timeout.is_zero() {
+            1
+        } else {
+            config.max_requests_per_connection.max(1)
+        },
+        use_sendfile: config.use_sendfile,
+        entity_tag_kind: config.entity_tag_kind,
+        content_hashes: conditional::ContentHashes::default(),
+        compression: config.compression,
+        compression_min_size: config.compression_min_size,
+        precompressed: config.precompressed,
+        cache_rules: cache_control::CacheRules::new(&config.cache_rules),
+        error_pages: config
+            .error_pages
+            .iter()
+            .map(|error_page| (error_page.status_code, error_page.path.to_string()))
+            .collect(),
+        https_redirect: None,
+        // Browsers ignore the header received over plain HTTP, so it is sent only with TLS
+        strict_transport_security: config.hsts_max_age.filter(|_| tls).map(|max_age| {
+            if config.hsts_include_subdomains {
+                format!("max-age={max_age}; includeSubDomains")
+            } else {
+                format!("max-age={max_age}")
+            }
+        }),
     }
 }
 
 #[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     #[error("TCP listener binding error: {0}")]
     TcpListenerBindingError(#[from] io::Error),
+    #[error("Unix socket binding error: {0}")]
+    UnixSocketBindingError(io::Error),
+    #[error("Socket activation error: {0}")]
+    SocketActivationError(io::Error),
+    #[error("Socket activation error: no socket is passed for the listener {0}")]
+    MissingActivatedSocketError(String),
+    #[error("systemd notification socket error: {0}")]
+    NotifySocketError(io::Error),
+    #[error("Ctrl-C wake-up socket error: {0}")]
+    WakePipeError(io::Error),
+    #[error("Root folder canonicalization error: {0}")]
+    RootFolderCanonicalizationError(io::Error),
+    #[error("MIME types loading error: {0}")]
+    MimeTypesLoadingError(#[from] mime::Error),
+    #[error("TLS certificates loading error: {0}")]
+    TlsCertificatesLoadingError(#[from] tls::Error),
 }
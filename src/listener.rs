@@ -0,0 +1,227 @@
+/// Binding of the listening sockets
+use std::{io, net};
+
+#[cfg(unix)]
+use std::{
+    fs,
+    os::unix::{
+        fs::DirBuilderExt, fs::FileTypeExt, fs::MetadataExt, fs::PermissionsExt, net as unix_net,
+    },
+    path,
+};
+
+/// Maximum length of the queue of pending connections, the same as std uses
+const BACKLOG: i32 = 128;
+
+/// Binds the TCP listener to the address.
+/// IPv6 listener accepts IPv4 connections too (as IPv4-mapped addresses) if dual_stack is true.
+/// IPV6_V6ONLY is always set explicitly, since its default value differs between the platforms.
+pub fn bind_tcp(
+    socket_addr: net::SocketAddr,
+    dual_stack: bool,
+) -> Result<net::TcpListener, io::Error> {
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(socket_addr),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+    if socket_addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    // As std does, the address can be bound again right after the restart.
+    // On Windows this option allows other sockets to steal the port, so it is not set there.
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    socket.bind(&socket_addr.into())?;
+    socket.listen(BACKLOG)?;
+    Ok(socket.into())
+}
+
+/// Address of the peer for the logs.
+/// IPv4 clients of the dual-stack listener are shown as "127.0.0.1:5000", not "[::ffff:127.0.0.1]:5000".
+pub fn display_peer_addr(peer_addr: net::SocketAddr) -> net::SocketAddr {
+    net::SocketAddr::new(peer_addr.ip().to_canonical(), peer_addr.port())
+}
+
+/// Unix domain socket listener, the socket file is removed when the listener is dropped
+#[cfg(unix)]
+pub struct UnixSocketListener {
+    listener: unix_net::UnixListener,
+    path: path::PathBuf,
+    /// Device and inode of the socket file, another server may have replaced the file at the same path.
+    /// None if the socket is not bound by the server, its file is not removed then.
+    file_id: Option<(u64, u64)>,
+}
+
+#[cfg(unix)]
+impl UnixSocketListener {
+    /// Wraps the listener bound by another process (systemd socket activation)
+    pub fn adopt(listener: unix_net::UnixListener) -> Self {
+        let path = listener
+            .local_addr()
+            .ok()
+            .and_then(|addr| addr.as_pathname().map(path::Path::to_path_buf))
+            .unwrap_or_default();
+        Self {
+            listener,
+            path,
+            file_id: None,
+        }
+    }
+
+    pub fn listener(&self) -> &unix_net::UnixListener {
+        &self.listener
+    }
+
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let Some(file_id) = self.file_id else {
+            return;
+        };
+        let is_own_file = fs::symlink_metadata(&self.path)
+            .is_ok_and(|metadata| (metadata.dev(), metadata.ino()) == file_id);
+        if is_own_file {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Binds the Unix domain socket listener to the path and sets the permission bits of the socket file.
+/// The stale socket file left by the previous run (nobody accepts connections on it) is removed,
+/// but the socket of the running server and the files of other types are not touched.
+#[cfg(unix)]
+pub fn bind_unix(path: &path::Path, mode: Option<u32>) -> Result<UnixSocketListener, io::Error> {
+    remove_stale_socket(path)?;
+    let listener = match mode {
+        Some(mode) => bind_unix_with_mode(path, mode)?,
+        None => unix_net::UnixListener::bind(path)?,
+    };
+    let metadata = fs::symlink_metadata(path)?;
+    Ok(UnixSocketListener {
+        listener,
+        path: path.to_path_buf(),
+        file_id: Some((metadata.dev(), metadata.ino())),
+    })
+}
+
+/// The socket file gets the umask-derived permissions when it is bound, and the clients could connect before chmod.
+/// So the socket is bound in the private folder (only the owner can reach the files in it) next to the path,
+/// gets the permission bits and is moved to the path, where it appears with the final permissions.
+#[cfg(unix)]
+fn bind_unix_with_mode(path: &path::Path, mode: u32) -> Result<unix_net::UnixListener, io::Error> {
+    let file_name = path.file_name().ok_or(io::ErrorKind::InvalidInput)?;
+    let mut private_folder_name = std::ffi::OsString::from(".");
+    private_folder_name.push(file_name);
+    private_folder_name.push(format!(".{}", std::process::id()));
+    let private_folder_path = path.with_file_name(private_folder_name);
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_folder_path)?;
+    let private_path = private_folder_path.join("socket");
+    let result = unix_net::UnixListener::bind(&private_path).and_then(|listener| {
+        fs::set_permissions(&private_path, fs::Permissions::from_mode(mode))?;
+        fs::rename(&private_path, path)?;
+        Ok(listener)
+    });
+    // The socket file is left in the folder only if the binding has failed
+    let _ = fs::remove_file(&private_path);
+    let _ = fs::remove_dir(&private_folder_path);
+    result
+}
+
+#[cfg(unix)]
+fn remove_stale_socket(path: &path::Path) -> Result<(), io::Error> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+    match unix_net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is used by another server", path.display()),
+        )),
+        Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(path),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dual_stack_listener() {
+        let listener = bind_tcp("[::]:0".parse().unwrap(), true).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let client = net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let (_, peer_addr) = listener.accept().unwrap();
+        assert!(peer_addr.is_ipv6());
+        assert_eq!(display_peer_addr(peer_addr), client.local_addr().unwrap());
+    }
+
+    #[test]
+    fn ipv6_only_listener() {
+        let listener = bind_tcp("[::]:0".parse().unwrap(), false).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(net::TcpStream::connect(("127.0.0.1", port)).is_err());
+        let client = net::TcpStream::connect(("::1", port)).unwrap();
+        let (_, peer_addr) = listener.accept().unwrap();
+        assert_eq!(display_peer_addr(peer_addr), client.local_addr().unwrap());
+        assert_eq!(
+            display_peer_addr(peer_addr).to_string(),
+            format!("[::1]:{}", peer_addr.port())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_listener() {
+        let path = std::env::temp_dir().join("simple_web_server_listener_unix.sock");
+        let _ = fs::remove_file(&path);
+        // Stale socket of the previous run
+        drop(unix_net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let listener = bind_unix(&path, Some(0o660)).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+        // The private folder the socket is bound in is removed
+        let private_folder_path = path.with_file_name(format!(
+            ".simple_web_server_listener_unix.sock.{}",
+            std::process::id()
+        ));
+        assert!(!private_folder_path.exists());
+        // The socket is in use
+        let error = bind_unix(&path, None).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+
+        let _client = unix_net::UnixStream::connect(&path).unwrap();
+        assert!(listener.listener().accept().is_ok());
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_over_regular_file() {
+        let path = std::env::temp_dir().join("simple_web_server_listener_regular.sock");
+        fs::write(&path, "content").unwrap();
+        let error = bind_unix(&path, None).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "content");
+        fs::remove_file(&path).unwrap();
+    }
+}
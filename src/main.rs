@@ -1,5 +1,5 @@
 use simple_web_server::{config, Server};
-use std::{net, path, process};
+use std::{io, net, path, process, time};
 
 fn main() -> process::ExitCode {
     // Arguments parsing
@@ -8,9 +8,88 @@ fn main() -> process::ExitCode {
         "Starting the server.\n\
         Current configuration:\n\
         Addr: {}\n\
+        Additional listeners: {}\n\
+        Unix socket: {}\n\
+        Socket activation: {}\n\
         Root folder {}\n\
-        Threads number: {}",
-        args.socket_addr_v4, args.root_folder_path, args.threads_number
+        Threads number: {}\n\
+        MIME types file: {}\n\
+        Default MIME type: {}\n\
+        Index files: {}\n\
+        Autoindex: {}\n\
+        Keep-alive timeout: {} s\n\
+        Max requests per connection: {}\n\
+        ETag: {}\n\
+        Compression: {}\n\
+        Precompressed files: {}\n\
+        Cache-Control rules: {}\n\
+        Error pages: {}\n\
+        TLS: {}\n\
+        HTTPS redirect: {}\n\
+        Strict-Transport-Security: {}",
+        if args.dual_stack {
+            format!("{} (dual-stack)", args.socket_addr)
+        } else {
+            args.socket_addr.clone()
+        },
+        if args.listeners.is_empty() {
+            "none".to_string()
+        } else {
+            args.listeners.join("; ")
+        },
+        match (&args.unix_socket_path, &args.unix_socket_mode) {
+            (Some(unix_socket_path), Some(unix_socket_mode)) =>
+                format!("{unix_socket_path} (mode {unix_socket_mode})"),
+            (Some(unix_socket_path), None) => unix_socket_path.clone(),
+            (None, _) => "off".to_string(),
+        },
+        args.socket_activation,
+        args.root_folder_path,
+        args.threads_number,
+        args.mime_types_file_path.as_deref().unwrap_or("none"),
+        args.default_mime_type,
+        args.index_file_names.join(", "),
+        args.autoindex,
+        args.keep_alive_timeout,
+        args.max_requests_per_connection,
+        args.entity_tag_kind,
+        if args.no_compression {
+            "off".to_string()
+        } else {
+            format!("from {} bytes", args.compression_min_size)
+        },
+        args.precompressed,
+        if args.cache_rules.is_empty() {
+            "none".to_string()
+        } else {
+            args.cache_rules.join("; ")
+        },
+        if args.error_pages.is_empty() {
+            "built-in".to_string()
+        } else {
+            args.error_pages.join("; ")
+        },
+        match &args.tls_cert_chain_path {
+            Some(tls_cert_chain_path) if args.tls_host_certificates.is_empty() =>
+                tls_cert_chain_path.to_string(),
+            Some(tls_cert_chain_path) => format!(
+                "{tls_cert_chain_path}; {}",
+                args.tls_host_certificates.join("; ")
+            ),
+            None if args.tls_host_certificates.is_empty() => "off".to_string(),
+            None => args.tls_host_certificates.join("; "),
+        },
+        match &args.https_redirect_addr {
+            Some(https_redirect_addr) =>
+                format!("{https_redirect_addr} ({})", args.https_redirect_status),
+            None => "off".to_string(),
+        },
+        match args.hsts_max_age {
+            Some(hsts_max_age) if args.hsts_include_subdomains =>
+                format!("max-age={hsts_max_age}; includeSubDomains"),
+            Some(hsts_max_age) => format!("max-age={hsts_max_age}"),
+            None => "off".to_string(),
+        }
     );
 
     // Config building
@@ -33,27 +112,128 @@ fn main() -> process::ExitCode {
 
     server.run();
 
-    return process::ExitCode::SUCCESS;
+    process::ExitCode::SUCCESS
 }
 
 /// Simple multithreaded web server
 #[derive(clap::Parser, Debug)]
 struct Args {
-    /// IP address and port that the server is listening on. It must be in the format IP:PORT.
-    /// 127.0.0.1:7878 for example.
+    /// IP address (or host name resolved at startup) and port that the server is listening on.
+    /// It must be in the format IP:PORT or HOST:PORT: 127.0.0.1:7878, [::1]:7878 or localhost:7878 for example.
     #[arg(id = "addr", short, long, default_value = "127.0.0.1:7878")]
-    socket_addr_v4: String,
+    socket_addr: String,
+    /// Accept IPv4 connections on the IPv6 addresses ([::]:7878 for example) too, by turning IPV6_V6ONLY off.
+    #[arg(long)]
+    dual_stack: bool,
+    /// Additional listener in the format ADDR[;root=PATH][;tls=off|CERT_PATH,KEY_PATH], can be repeated.
+    /// The listener uses the common root folder and TLS certificates unless they are set:
+    /// "127.0.0.1:8081;root=./admin;tls=off", "[::]:8443;tls=site.crt,site.key".
+    #[arg(id = "listen", long)]
+    listeners: Vec<String>,
+    /// Path of the Unix domain socket the server is listening on too, for a reverse proxy on the same host.
+    /// It serves plain HTTP from the root folder. The stale socket file left by the previous run is removed.
+    #[arg(id = "unix_socket", long)]
+    unix_socket_path: Option<String>,
+    /// Permission bits of the Unix socket file in octal, 660 for example.
+    #[arg(id = "unix_socket_mode", long, requires = "unix_socket")]
+    unix_socket_mode: Option<String>,
+    /// Use the listening sockets passed by systemd (socket activation) instead of binding the addresses.
+    /// The sockets bound to the addresses of the listeners get their root folder and TLS settings, others get the common ones.
+    /// The additional listeners, the Unix socket and the HTTPS redirect must have their passed sockets.
+    /// Linux only.
+    #[arg(long)]
+    socket_activation: bool,
     /// Path to the folder that contains the site files.
     #[arg(id = "root_folder", short, long, default_value = "./www")]
     root_folder_path: String,
     /// Number of threads that serve connections. Max 255.
     #[arg(short, long, default_value_t = 8)]
     threads_number: u8,
+    /// Path to the file with additional extension to MIME type mappings.
+    /// Each line contains the MIME type followed by its extensions, "text/html html htm" for example.
+    #[arg(id = "mime_types", long)]
+    mime_types_file_path: Option<String>,
+    /// MIME type of files with unknown extensions.
+    #[arg(long, default_value = simple_web_server::mime::DEFAULT_MIME_TYPE)]
+    default_mime_type: String,
+    /// Names of the files that are served when a folder is requested, in order of priority.
+    #[arg(
+        id = "index",
+        long,
+        value_delimiter = ',',
+        default_value = "index.html,index.htm"
+    )]
+    index_file_names: Vec<String>,
+    /// List the contents of folders that have no index file.
+    #[arg(long)]
+    autoindex: bool,
+    /// Include hidden files (names starting with '.') in folder listings.
+    #[arg(long)]
+    autoindex_show_hidden: bool,
+    /// How long (in seconds) an idle persistent connection waits for the next request. 0 disables keep-alive.
+    #[arg(long, default_value_t = 5)]
+    keep_alive_timeout: u64,
+    /// Maximum number of requests served over one persistent connection. 0 disables keep-alive.
+    #[arg(long, default_value_t = 100)]
+    max_requests_per_connection: u32,
+    /// Copy files through userspace buffers instead of sending them by sendfile(2) on Linux.
+    #[arg(long)]
+    no_sendfile: bool,
+    /// How the ETag of the files is formed: "strong" or "weak" (from the file size, modification time and inode)
+    /// or "hash" (strong, from the hash of the file content).
+    #[arg(id = "etag", long, default_value = "strong")]
+    entity_tag_kind: String,
+    /// Send all responses uncompressed.
+    #[arg(long)]
+    no_compression: bool,
+    /// Responses shorter than this number of bytes are not compressed.
+    #[arg(long, default_value_t = 1024)]
+    compression_min_size: u64,
+    /// Serve the precompressed sibling files ("app.js.br", "app.js.zst", "app.js.gz" for "app.js")
+    /// to the clients that accept their encoding.
+    #[arg(long)]
+    precompressed: bool,
+    /// Cache-Control rule in the format PATTERN=VALUE, can be repeated, the first matching rule is applied.
+    /// Patterns without '/' are matched against the file name, other patterns against the path from the root folder:
+    /// "*.html=no-cache", "/static/**=public, immutable, max-age=31536000".
+    /// The Expires header is computed from max-age.
+    #[arg(id = "cache_control", long)]
+    cache_rules: Vec<String>,
+    /// Custom page of the 4xx and 5xx statuses in the format CODE[,CODE...]=/PATH, can be repeated.
+    /// The path is relative to the root folder: "404=/errors/404.html", "500,502,503=/errors/50x.html".
+    /// The built-in page is sent if the file is missing.
+    #[arg(id = "error_page", long)]
+    error_pages: Vec<String>,
+    /// Path to the PEM file with the certificate chain of the default TLS certificate, HTTPS is served if it is set.
+    #[arg(id = "tls_cert", long, requires = "tls_key")]
+    tls_cert_chain_path: Option<String>,
+    /// Path to the PEM file with the private key of the default TLS certificate.
+    #[arg(id = "tls_key", long, requires = "tls_cert")]
+    tls_private_key_path: Option<String>,
+    /// TLS certificate for the server name (SNI) in the format HOST=CERT_PATH,KEY_PATH, can be repeated:
+    /// "example.com=example.crt,example.key", "*.example.com=wildcard.crt,wildcard.key".
+    /// The default certificate (or the first one) is used for other names.
+    /// The certificate files are reloaded when they change, without restarting the server.
+    #[arg(id = "tls_sni", long)]
+    tls_host_certificates: Vec<String>,
+    /// IP address and port of the plain HTTP listener that redirects all requests to HTTPS, 0.0.0.0:80 for example.
+    /// It requires TLS.
+    #[arg(id = "https_redirect", long)]
+    https_redirect_addr: Option<String>,
+    /// Status of the redirect to HTTPS: 301 or 308 (the clients keep the method and the body).
+    #[arg(long, default_value_t = 301)]
+    https_redirect_status: u16,
+    /// Send the Strict-Transport-Security header with this max-age (in seconds) in the HTTPS responses.
+    #[arg(id = "hsts", long)]
+    hsts_max_age: Option<u64>,
+    /// Apply Strict-Transport-Security to the subdomains.
+    #[arg(long, requires = "hsts")]
+    hsts_include_subdomains: bool,
 }
 
 impl Args {
-    pub fn build_config(&self) -> Result<config::Config, config::Error> {
-        let socket_addr_v4 = self.socket_addr_v4.parse::<net::SocketAddrV4>()?;
+    pub fn build_config(&self) -> Result<config::Config<'_>, config::Error> {
+        let socket_addr = resolve_socket_addr(&self.socket_addr)?;
         let root_folder_path = path::Path::new(self.root_folder_path.as_str());
         if !root_folder_path.is_dir() {
             // The path does not exist or does not point to the directory or cannot be accessed.
@@ -64,35 +244,351 @@ impl Args {
             return Err(config::Error::ZeroThreadsNumber);
         }
 
+        let mime_types_file_path = self.mime_types_file_path.as_deref().map(path::Path::new);
+        if let Some(mime_types_file_path) = mime_types_file_path {
+            if !mime_types_file_path.is_file() {
+                return Err(config::Error::WrongMimeTypesFilePath);
+            }
+        }
+        let default_mime_type = self.default_mime_type.as_str();
+        if !default_mime_type.contains('/') {
+            return Err(config::Error::WrongDefaultMimeType);
+        }
+        let index_file_names: Vec<&str> =
+            self.index_file_names.iter().map(String::as_str).collect();
+        for index_file_name in &index_file_names {
+            // Index file must be a file name, not a path
+            if index_file_name.is_empty()
+                || *index_file_name == "."
+                || *index_file_name == ".."
+                || index_file_name.contains(['/', '\\'])
+            {
+                return Err(config::Error::WrongIndexFileName);
+            }
+        }
+
+        let entity_tag_kind = match self.entity_tag_kind.as_str() {
+            "strong" => config::EntityTagKind::Strong,
+            "weak" => config::EntityTagKind::Weak,
+            "hash" => config::EntityTagKind::ContentHash,
+            _ => return Err(config::Error::WrongEntityTagKind),
+        };
+
+        let mut cache_rules = Vec::new();
+        for cache_rule in &self.cache_rules {
+            let Some((pattern, cache_control)) = cache_rule.split_once('=') else {
+                return Err(config::Error::WrongCacheRule);
+            };
+            let (pattern, cache_control) = (pattern.trim(), cache_control.trim());
+            // The value is sent in the header as is, so it must not contain line breaks
+            if pattern.is_empty()
+                || cache_control.is_empty()
+                || cache_control.chars().any(char::is_control)
+            {
+                return Err(config::Error::WrongCacheRule);
+            }
+            // A huge max-age would make the Expires time unrepresentable
+            for directive in cache_control.split(',') {
+                let Some((name, value)) = directive.split_once('=') else {
+                    continue;
+                };
+                if name.trim().eq_ignore_ascii_case("max-age") {
+                    let value = value.trim().trim_matches('"');
+                    match value.parse::<u64>() {
+                        Ok(max_age)
+                            if max_age <= config::MAX_AGE_LIMIT && !value.starts_with('+') => {}
+                        _ => return Err(config::Error::WrongCacheRule),
+                    }
+                }
+            }
+            cache_rules.push(config::CacheRule {
+                pattern,
+                cache_control,
+            });
+        }
+
+        let mut error_pages = Vec::new();
+        for error_page in &self.error_pages {
+            let Some((status_codes, path)) = error_page.split_once('=') else {
+                return Err(config::Error::WrongErrorPage);
+            };
+            let path = path.trim();
+            // The page must be inside the root folder
+            if !path.starts_with('/') || path.split(['/', '\\']).any(|segment| segment == "..") {
+                return Err(config::Error::WrongErrorPage);
+            }
+            for status_code in status_codes.split(',') {
+                let status_code = status_code.trim().parse::<u16>();
+                match status_code {
+                    Ok(status_code) if (400..=599).contains(&status_code) => {
+                        error_pages.push(config::ErrorPage { status_code, path })
+                    }
+                    _ => return Err(config::Error::WrongErrorPage),
+                }
+            }
+        }
+
+        let mut tls_certificates = Vec::new();
+        if let (Some(tls_cert_chain_path), Some(tls_private_key_path)) =
+            (&self.tls_cert_chain_path, &self.tls_private_key_path)
+        {
+            tls_certificates.push(tls_certificate(
+                None,
+                tls_cert_chain_path,
+                tls_private_key_path,
+            )?);
+        }
+        for tls_host_certificate in &self.tls_host_certificates {
+            let Some((host_name, paths)) = tls_host_certificate.split_once('=') else {
+                return Err(config::Error::WrongTlsCertificate);
+            };
+            let Some((cert_chain_path, private_key_path)) = paths.split_once(',') else {
+                return Err(config::Error::WrongTlsCertificate);
+            };
+            let host_name = host_name.trim();
+            if host_name.is_empty() || host_name.contains(char::is_whitespace) {
+                return Err(config::Error::WrongTlsCertificate);
+            }
+            tls_certificates.push(tls_certificate(
+                Some(host_name),
+                cert_chain_path.trim(),
+                private_key_path.trim(),
+            )?);
+        }
+
+        let mut listeners = vec![config::Listener {
+            socket_addr,
+            root_folder_path: None,
+            tls_certificates: None,
+        }];
+        for listener in &self.listeners {
+            listeners.push(parse_listener(listener)?);
+        }
+        let unix_socket = match &self.unix_socket_path {
+            Some(unix_socket_path) => Some(unix_socket(
+                unix_socket_path,
+                self.unix_socket_mode.as_deref(),
+            )?),
+            None => None,
+        };
+        // The redirect and Strict-Transport-Security need at least one HTTPS listener
+        let has_tls = listeners.iter().any(|listener| {
+            !listener
+                .tls_certificates
+                .as_ref()
+                .unwrap_or(&tls_certificates)
+                .is_empty()
+        });
+
+        let https_redirect = match &self.https_redirect_addr {
+            Some(https_redirect_addr) => {
+                let socket_addr = resolve_socket_addr(https_redirect_addr)?;
+                let status_code = self.https_redirect_status;
+                if !has_tls || !matches!(status_code, 301 | 308) {
+                    return Err(config::Error::WrongHttpsRedirect);
+                }
+                Some(config::HttpsRedirect {
+                    socket_addr,
+                    status_code,
+                })
+            }
+            None => None,
+        };
+        if self.hsts_max_age.is_some() && !has_tls {
+            return Err(config::Error::HstsWithoutTls);
+        }
+
         Ok(config::Config {
-            socket_addr_v4,
+            listeners,
+            dual_stack: self.dual_stack,
+            socket_activation: self.socket_activation,
+            unix_socket,
             root_folder_path,
             threads_number,
+            mime_types_file_path,
+            default_mime_type,
+            index_file_names,
+            autoindex: self.autoindex,
+            autoindex_show_hidden: self.autoindex_show_hidden,
+            keep_alive_timeout: time::Duration::from_secs(self.keep_alive_timeout),
+            max_requests_per_connection: self.max_requests_per_connection,
+            use_sendfile: !self.no_sendfile,
+            entity_tag_kind,
+            compression: !self.no_compression,
+            compression_min_size: self.compression_min_size,
+            precompressed: self.precompressed,
+            cache_rules,
+            error_pages,
+            tls_certificates,
+            https_redirect,
+            hsts_max_age: self.hsts_max_age,
+            hsts_include_subdomains: self.hsts_include_subdomains,
         })
     }
 }
 
+/// Parses the socket address, the host names ("localhost:7878") are resolved, the first address is used
+fn resolve_socket_addr(addr: &str) -> Result<net::SocketAddr, config::Error> {
+    let error = match addr.parse::<net::SocketAddr>() {
+        Ok(socket_addr) => return Ok(socket_addr),
+        Err(error) => error,
+    };
+    // Only HOST:PORT strings are resolved, other wrong addresses are reported as is
+    let is_host_and_port = addr.rsplit_once(':').is_some_and(|(host, port)| {
+        !host.is_empty() && !host.contains(['[', ']', ':']) && port.parse::<u16>().is_ok()
+    });
+    if !is_host_and_port {
+        return Err(config::Error::WrongAddr(error));
+    }
+    let mut socket_addrs =
+        net::ToSocketAddrs::to_socket_addrs(addr).map_err(config::Error::AddrResolutionError)?;
+    socket_addrs.next().ok_or_else(|| {
+        config::Error::AddrResolutionError(io::Error::new(io::ErrorKind::NotFound, "no addresses"))
+    })
+}
+
+/// Parses the additional listener: "127.0.0.1:8081;root=./admin;tls=off"
+fn parse_listener(listener: &str) -> Result<config::Listener<'_>, config::Error> {
+    let mut parts = listener.split(';');
+    let socket_addr = resolve_socket_addr(parts.next().unwrap_or_default().trim())?;
+    let mut root_folder_path = None;
+    let mut tls_certificates = None;
+    for part in parts {
+        let Some((name, value)) = part.split_once('=') else {
+            return Err(config::Error::WrongListener);
+        };
+        match (name.trim(), value.trim()) {
+            ("root", path) if root_folder_path.is_none() => {
+                let path = path::Path::new(path);
+                if !path.is_dir() {
+                    return Err(config::Error::WrongRootFolderPath);
+                }
+                root_folder_path = Some(path);
+            }
+            ("tls", "off") if tls_certificates.is_none() => tls_certificates = Some(Vec::new()),
+            ("tls", paths) if tls_certificates.is_none() => {
+                let Some((cert_chain_path, private_key_path)) = paths.split_once(',') else {
+                    return Err(config::Error::WrongListener);
+                };
+                tls_certificates = Some(vec![tls_certificate(
+                    None,
+                    cert_chain_path.trim(),
+                    private_key_path.trim(),
+                )?]);
+            }
+            _ => return Err(config::Error::WrongListener),
+        }
+    }
+    Ok(config::Listener {
+        socket_addr,
+        root_folder_path,
+        tls_certificates,
+    })
+}
+
+/// Checks the Unix socket path and parses its octal permission bits ("660" or "0660")
+fn unix_socket<'a>(
+    path: &'a str,
+    mode: Option<&str>,
+) -> Result<config::UnixSocket<'a>, config::Error> {
+    let path = path::Path::new(path);
+    // The socket file is created when the server is initialized, its folder must exist
+    let folder_path = match path.parent() {
+        Some(folder_path) if !folder_path.as_os_str().is_empty() => folder_path,
+        _ => path::Path::new("."),
+    };
+    if path.file_name().is_none() || !folder_path.is_dir() {
+        return Err(config::Error::WrongUnixSocket);
+    }
+    let mode = match mode {
+        Some(mode) => match u32::from_str_radix(mode.trim(), 8) {
+            Ok(mode) if mode <= 0o777 => Some(mode),
+            _ => return Err(config::Error::WrongUnixSocket),
+        },
+        None => None,
+    };
+    Ok(config::UnixSocket { path, mode })
+}
+
+/// Checks that the certificate and the private key files exist, they are loaded when the server is initialized
+fn tls_certificate<'a>(
+    host_name: Option<&'a str>,
+    cert_chain_path: &'a str,
+    private_key_path: &'a str,
+) -> Result<config::TlsCertificate<'a>, config::Error> {
+    let cert_chain_path = path::Path::new(cert_chain_path);
+    let private_key_path = path::Path::new(private_key_path);
+    if !cert_chain_path.is_file() || !private_key_path.is_file() {
+        return Err(config::Error::WrongTlsCertificate);
+    }
+    Ok(config::TlsCertificate {
+        host_name,
+        cert_chain_path,
+        private_key_path,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::Args;
+    use std::mem;
+
+    /// Arguments with default values
+    fn default_args() -> Args {
+        clap::Parser::parse_from(["simple_web_server"])
+    }
+
     #[test]
     fn build_config_from_args_wrong_addr() {
         let args = Args {
-            socket_addr_v4: "Wrong".to_string(),
+            socket_addr: "Wrong".to_string(),
             root_folder_path: "./".to_string(),
             threads_number: 4,
+            ..default_args()
         };
         let config = args.build_config();
         assert!(matches!(config, Err(config::Error::WrongAddr(_))));
     }
 
+    #[test]
+    fn build_config_from_args_addresses() {
+        let socket_addr = |addr: &str| {
+            let args = Args {
+                socket_addr: addr.to_string(),
+                ..default_args()
+            };
+            args.build_config()
+                .map(|config| config.listeners[0].socket_addr)
+        };
+        assert_eq!(
+            socket_addr("[::]:8080").unwrap(),
+            net::SocketAddr::from((net::Ipv6Addr::UNSPECIFIED, 8080))
+        );
+        assert_eq!(
+            socket_addr("[fe80::1%2]:80").unwrap().to_string(),
+            "[fe80::1%2]:80"
+        );
+        assert!(socket_addr("localhost:8080").unwrap().ip().is_loopback());
+        for addr in ["::1:8080", "[::1]", "localhost", "127.0.0.1:99999"] {
+            assert!(
+                matches!(socket_addr(addr), Err(config::Error::WrongAddr(_))),
+                "{addr}"
+            );
+        }
+        assert!(matches!(
+            socket_addr("host.invalid:8080"),
+            Err(config::Error::AddrResolutionError(_))
+        ));
+    }
+
     #[test]
     fn build_config_from_args_wrong_path() {
         let args = Args {
-            socket_addr_v4: "127.0.0.1:7878".to_string(),
+            socket_addr: "127.0.0.1:7878".to_string(),
             root_folder_path: "".to_string(),
             threads_number: 4,
+            ..default_args()
         };
         let config = args.build_config();
         assert!(matches!(config, Err(config::Error::WrongRootFolderPath)));
@@ -101,9 +597,10 @@ mod tests {
     #[test]
     fn build_config_from_args_zero_threads_number() {
         let args = Args {
-            socket_addr_v4: "127.0.0.1:7878".to_string(),
+            socket_addr: "127.0.0.1:7878".to_string(),
             root_folder_path: "./".to_string(),
             threads_number: 0,
+            ..default_args()
         };
         let config = args.build_config();
         assert!(matches!(config, Err(config::Error::ZeroThreadsNumber)));
@@ -112,11 +609,382 @@ mod tests {
     #[test]
     fn build_config_from_args() {
         let args = Args {
-            socket_addr_v4: "127.0.0.1:7878".to_string(),
+            socket_addr: "127.0.0.1:7878".to_string(),
             root_folder_path: "./".to_string(),
             threads_number: 4,
+            ..default_args()
         };
         let config = args.build_config();
         assert!(config.is_ok());
     }
+
+    #[test]
+    fn build_config_from_args_wrong_mime_types_file_path() {
+        let args = Args {
+            mime_types_file_path: Some("./does_not_exist.types".to_string()),
+            ..default_args()
+        };
+        let config = args.build_config();
+        assert!(matches!(config, Err(config::Error::WrongMimeTypesFilePath)));
+    }
+
+    #[test]
+    fn build_config_from_args_wrong_default_mime_type() {
+        let args = Args {
+            default_mime_type: "binary".to_string(),
+            ..default_args()
+        };
+        let config = args.build_config();
+        assert!(matches!(config, Err(config::Error::WrongDefaultMimeType)));
+    }
+
+    #[test]
+    fn build_config_from_args_wrong_index_file_name() {
+        let args = Args {
+            index_file_names: vec!["index.html".to_string(), "../index.html".to_string()],
+            ..default_args()
+        };
+        let config = args.build_config();
+        assert!(matches!(config, Err(config::Error::WrongIndexFileName)));
+    }
+
+    #[test]
+    fn build_config_from_args_entity_tag_kind() {
+        let args = Args {
+            entity_tag_kind: "hash".to_string(),
+            ..default_args()
+        };
+        let config = args.build_config().unwrap();
+        assert_eq!(config.entity_tag_kind, config::EntityTagKind::ContentHash);
+
+        let args = Args {
+            entity_tag_kind: "md5".to_string(),
+            ..default_args()
+        };
+        let config = args.build_config();
+        assert!(matches!(config, Err(config::Error::WrongEntityTagKind)));
+    }
+
+    #[test]
+    fn build_config_from_args_listeners() {
+        let cert_chain_path = std::env::temp_dir().join("simple_web_server_main_listener.crt");
+        let private_key_path = std::env::temp_dir().join("simple_web_server_main_listener.key");
+        std::fs::write(&cert_chain_path, "").unwrap();
+        std::fs::write(&private_key_path, "").unwrap();
+        let cert_chain = cert_chain_path.to_str().unwrap();
+        let private_key = private_key_path.to_str().unwrap();
+
+        let args = Args {
+            socket_addr: "127.0.0.1:8080".to_string(),
+            listeners: vec![
+                "127.0.0.1:8081; root=./src; tls=off".to_string(),
+                format!("[::]:8443;tls={cert_chain},{private_key}"),
+            ],
+            hsts_max_age: Some(60),
+            ..default_args()
+        };
+        let config = args.build_config().unwrap();
+        assert_eq!(
+            config.listeners,
+            [
+                config::Listener {
+                    socket_addr: net::SocketAddr::from((net::Ipv4Addr::LOCALHOST, 8080)),
+                    root_folder_path: None,
+                    tls_certificates: None,
+                },
+                config::Listener {
+                    socket_addr: net::SocketAddr::from((net::Ipv4Addr::LOCALHOST, 8081)),
+                    root_folder_path: Some(path::Path::new("./src")),
+                    tls_certificates: Some(Vec::new()),
+                },
+                config::Listener {
+                    socket_addr: net::SocketAddr::from((net::Ipv6Addr::UNSPECIFIED, 8443)),
+                    root_folder_path: None,
+                    tls_certificates: Some(vec![config::TlsCertificate {
+                        host_name: None,
+                        cert_chain_path: &cert_chain_path,
+                        private_key_path: &private_key_path,
+                    }]),
+                },
+            ]
+        );
+
+        for (listener, expected_error) in [
+            ("127.0.0.1:8081;root", config::Error::WrongListener),
+            ("127.0.0.1:8081;port=80", config::Error::WrongListener),
+            (
+                "127.0.0.1:8081;tls=off;tls=off",
+                config::Error::WrongListener,
+            ),
+            (
+                "127.0.0.1:8081;tls=missing.crt",
+                config::Error::WrongListener,
+            ),
+            (
+                "127.0.0.1:8081;root=./missing",
+                config::Error::WrongRootFolderPath,
+            ),
+        ] {
+            let args = Args {
+                listeners: vec![listener.to_string()],
+                ..default_args()
+            };
+            let error = args.build_config().unwrap_err();
+            assert_eq!(
+                mem::discriminant(&error),
+                mem::discriminant(&expected_error),
+                "{listener}"
+            );
+        }
+        // Strict-Transport-Security needs an HTTPS listener
+        let args = Args {
+            listeners: vec!["127.0.0.1:8081".to_string()],
+            hsts_max_age: Some(60),
+            ..default_args()
+        };
+        let config = args.build_config();
+        assert!(matches!(config, Err(config::Error::HstsWithoutTls)));
+
+        let _ = std::fs::remove_file(&cert_chain_path);
+        let _ = std::fs::remove_file(&private_key_path);
+    }
+
+    #[test]
+    fn build_config_from_args_unix_socket() {
+        let unix_socket = |path: &str, mode: Option<&str>| {
+            let args = Args {
+                unix_socket_path: Some(path.to_string()),
+                unix_socket_mode: mode.map(str::to_string),
+                ..default_args()
+            };
+            args.build_config().map(|config| {
+                let unix_socket = config.unix_socket.unwrap();
+                (unix_socket.path.to_path_buf(), unix_socket.mode)
+            })
+        };
+        assert_eq!(
+            unix_socket("./server.sock", Some("660")).unwrap(),
+            (path::PathBuf::from("./server.sock"), Some(0o660))
+        );
+        assert_eq!(
+            unix_socket("server.sock", Some("0777")).unwrap(),
+            (path::PathBuf::from("server.sock"), Some(0o777))
+        );
+        assert_eq!(
+            unix_socket("server.sock", None).unwrap(),
+            (path::PathBuf::from("server.sock"), None)
+        );
+        for (path, mode) in [
+            ("./missing/server.sock", None),
+            ("/", None),
+            ("server.sock", Some("1777")),
+            ("server.sock", Some("rw")),
+            ("server.sock", Some("")),
+        ] {
+            assert!(
+                matches!(unix_socket(path, mode), Err(config::Error::WrongUnixSocket)),
+                "{path} {mode:?}"
+            );
+        }
+        assert!(default_args().build_config().unwrap().unix_socket.is_none());
+    }
+
+    #[test]
+    fn build_config_from_args_tls_certificates() {
+        // Only the existence of the files is checked, they are loaded when the server is initialized
+        let cert_chain_path = std::env::temp_dir().join("simple_web_server_main_tls.crt");
+        let private_key_path = std::env::temp_dir().join("simple_web_server_main_tls.key");
+        std::fs::write(&cert_chain_path, "").unwrap();
+        std::fs::write(&private_key_path, "").unwrap();
+        let cert_chain = cert_chain_path.to_str().unwrap();
+        let private_key = private_key_path.to_str().unwrap();
+
+        let args = Args {
+            tls_cert_chain_path: Some(cert_chain.to_string()),
+            tls_private_key_path: Some(private_key.to_string()),
+            tls_host_certificates: vec![format!(" example.com = {cert_chain} , {private_key}")],
+            ..default_args()
+        };
+        let config = args.build_config().unwrap();
+        assert_eq!(
+            config.tls_certificates,
+            [
+                config::TlsCertificate {
+                    host_name: None,
+                    cert_chain_path: &cert_chain_path,
+                    private_key_path: &private_key_path,
+                },
+                config::TlsCertificate {
+                    host_name: Some("example.com"),
+                    cert_chain_path: &cert_chain_path,
+                    private_key_path: &private_key_path,
+                },
+            ]
+        );
+
+        for tls_host_certificate in [
+            format!("{cert_chain},{private_key}"),
+            format!("example.com={cert_chain}"),
+            format!("={cert_chain},{private_key}"),
+            format!("example.com={cert_chain},missing.key"),
+        ] {
+            let args = Args {
+                tls_host_certificates: vec![tls_host_certificate.clone()],
+                ..default_args()
+            };
+            let config = args.build_config();
+            assert!(
+                matches!(config, Err(config::Error::WrongTlsCertificate)),
+                "{tls_host_certificate}"
+            );
+        }
+        let _ = std::fs::remove_file(&cert_chain_path);
+        let _ = std::fs::remove_file(&private_key_path);
+    }
+
+    #[test]
+    fn build_config_from_args_https_redirect() {
+        let cert_chain_path = std::env::temp_dir().join("simple_web_server_main_redirect.crt");
+        let private_key_path = std::env::temp_dir().join("simple_web_server_main_redirect.key");
+        std::fs::write(&cert_chain_path, "").unwrap();
+        std::fs::write(&private_key_path, "").unwrap();
+        let tls_args = || Args {
+            tls_cert_chain_path: Some(cert_chain_path.to_str().unwrap().to_string()),
+            tls_private_key_path: Some(private_key_path.to_str().unwrap().to_string()),
+            ..default_args()
+        };
+
+        let args = Args {
+            https_redirect_addr: Some("0.0.0.0:8080".to_string()),
+            https_redirect_status: 308,
+            hsts_max_age: Some(31536000),
+            ..tls_args()
+        };
+        let config = args.build_config().unwrap();
+        assert_eq!(
+            config.https_redirect,
+            Some(config::HttpsRedirect {
+                socket_addr: net::SocketAddr::from((net::Ipv4Addr::UNSPECIFIED, 8080)),
+                status_code: 308,
+            })
+        );
+        assert_eq!(config.hsts_max_age, Some(31536000));
+
+        let args = Args {
+            https_redirect_addr: Some("0.0.0.0:8080".to_string()),
+            https_redirect_status: 302,
+            ..tls_args()
+        };
+        let config = args.build_config();
+        assert!(matches!(config, Err(config::Error::WrongHttpsRedirect)));
+        // Both features require TLS
+        let args = Args {
+            https_redirect_addr: Some("0.0.0.0:8080".to_string()),
+            ..default_args()
+        };
+        let config = args.build_config();
+        assert!(matches!(config, Err(config::Error::WrongHttpsRedirect)));
+        let args = Args {
+            hsts_max_age: Some(60),
+            ..default_args()
+        };
+        let config = args.build_config();
+        assert!(matches!(config, Err(config::Error::HstsWithoutTls)));
+
+        let _ = std::fs::remove_file(&cert_chain_path);
+        let _ = std::fs::remove_file(&private_key_path);
+    }
+
+    #[test]
+    fn build_config_from_args_error_pages() {
+        let args = Args {
+            error_pages: vec![
+                "404=/errors/404.html".to_string(),
+                "500, 503=/errors/50x.html".to_string(),
+            ],
+            ..default_args()
+        };
+        let config = args.build_config().unwrap();
+        assert_eq!(
+            config.error_pages,
+            [
+                config::ErrorPage {
+                    status_code: 404,
+                    path: "/errors/404.html"
+                },
+                config::ErrorPage {
+                    status_code: 500,
+                    path: "/errors/50x.html"
+                },
+                config::ErrorPage {
+                    status_code: 503,
+                    path: "/errors/50x.html"
+                },
+            ]
+        );
+
+        for error_page in [
+            "/errors/404.html",
+            "200=/index.html",
+            "301=/errors/moved.html",
+            "600=/errors/600.html",
+            "abc=/errors/404.html",
+            "404=errors/404.html",
+            "404=/../secret.html",
+        ] {
+            let args = Args {
+                error_pages: vec![error_page.to_string()],
+                ..default_args()
+            };
+            let config = args.build_config();
+            assert!(
+                matches!(config, Err(config::Error::WrongErrorPage)),
+                "{error_page}"
+            );
+        }
+    }
+
+    #[test]
+    fn build_config_from_args_cache_rules() {
+        let args = Args {
+            cache_rules: vec!["/static/** = public, max-age=60".to_string()],
+            ..default_args()
+        };
+        let config = args.build_config().unwrap();
+        assert_eq!(
+            config.cache_rules,
+            [config::CacheRule {
+                pattern: "/static/**",
+                cache_control: "public, max-age=60"
+            }]
+        );
+
+        // The greatest allowed max-age
+        let args = Args {
+            cache_rules: vec!["*.js=max-age=2147483648".to_string()],
+            ..default_args()
+        };
+        assert!(args.build_config().is_ok());
+
+        for cache_rule in [
+            "no-cache",
+            "*.html=",
+            "*.html=no-cache\r\nSet-Cookie: a=b",
+            "*.js=max-age=18446744073709551615",
+            "*.js=public, max-age=2147483649",
+            "*.js=max-age=-1",
+            "*.js=max-age=+60",
+            "*.js=max-age=",
+        ] {
+            let args = Args {
+                cache_rules: vec![cache_rule.to_string()],
+                ..default_args()
+            };
+            let config = args.build_config();
+            assert!(
+                matches!(config, Err(config::Error::WrongCacheRule)),
+                "{cache_rule}"
+            );
+        }
+    }
 }
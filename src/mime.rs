@@ -0,0 +1,177 @@
+/// MIME types detection
+use std::{collections::HashMap, fs, io, path};
+
+/// MIME type that is used when the default type is not specified
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// Built-in extension to MIME type table.
+/// Text types contain the charset, since the site files are expected to be in UTF-8.
+const BUILT_IN_MIME_TYPES: &[(&str, &str)] = &[
+    // Text
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("xml", "application/xml; charset=utf-8"),
+    ("json", "application/json; charset=utf-8"),
+    ("map", "application/json; charset=utf-8"),
+    ("webmanifest", "application/manifest+json; charset=utf-8"),
+    ("svg", "image/svg+xml; charset=utf-8"),
+    // Images
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("ico", "image/x-icon"),
+    // Fonts
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    // Audio and video
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    // Other
+    ("pdf", "application/pdf"),
+    ("wasm", "application/wasm"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+];
+
+/// Extension to MIME type mapping.
+#[derive(Debug, Clone)]
+pub struct MimeTypes {
+    // Keys are lowercase extensions without a dot
+    types: HashMap<String, String>,
+    default_type: String,
+}
+
+impl MimeTypes {
+    /// Creates a mapping that contains only built-in MIME types.
+    pub fn new(default_type: &str) -> Self {
+        let types = BUILT_IN_MIME_TYPES
+            .iter()
+            .map(|&(extension, mime_type)| (extension.to_string(), mime_type.to_string()))
+            .collect();
+        Self {
+            types,
+            default_type: default_type.to_string(),
+        }
+    }
+
+    /// Loads the user mapping file, its entries override the built-in ones.
+    /// The file has the format of "mime.types": each line contains the MIME type followed by its extensions,
+    /// "text/html html htm" for example. Empty lines and lines starting with '#' are ignored.
+    pub fn load_file(&mut self, path: &path::Path) -> Result<(), Error> {
+        let file_content = fs::read_to_string(path).map_err(Error::FileReadError)?;
+        self.parse(&file_content)
+    }
+
+    fn parse(&mut self, file_content: &str) -> Result<(), Error> {
+        for (line_index, line) in file_content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Entries in nginx format end with ';'
+            let line = line.trim_end_matches(';');
+            let mut words = line.split_whitespace();
+            let mime_type = words.next().unwrap();
+            if !mime_type.contains('/') {
+                return Err(Error::WrongLine(line_index + 1));
+            }
+            for extension in words {
+                let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+                self.types.insert(extension, mime_type.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the MIME type of the file based on its extension.
+    /// If the extension is unknown, the default type is returned.
+    pub fn get(&self, path: &path::Path) -> &str {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| self.types.get(&extension.to_ascii_lowercase()))
+            .unwrap_or(&self.default_type)
+    }
+}
+
+impl Default for MimeTypes {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIME_TYPE)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    #[error("Failed to read MIME types file: {0}")]
+    FileReadError(io::Error),
+    #[error("Wrong MIME type in line {0} of MIME types file")]
+    WrongLine(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_types() {
+        let mime_types = MimeTypes::default();
+        assert_eq!(
+            mime_types.get(path::Path::new("style.css")),
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(mime_types.get(path::Path::new("IMAGE.PNG")), "image/png");
+        assert_eq!(
+            mime_types.get(path::Path::new("archive.unknown")),
+            DEFAULT_MIME_TYPE
+        );
+        assert_eq!(mime_types.get(path::Path::new("README")), DEFAULT_MIME_TYPE);
+    }
+
+    #[test]
+    fn configured_default_type() {
+        let mime_types = MimeTypes::new("text/plain");
+        assert_eq!(mime_types.get(path::Path::new("file.xyz")), "text/plain");
+    }
+
+    #[test]
+    fn user_types_override_built_in() {
+        let mut mime_types = MimeTypes::default();
+        let file_content = "# comment\n\
+            \n\
+            application/x-custom  cst .CST2\n\
+            text/plain js;\n";
+        mime_types.parse(file_content).unwrap();
+        assert_eq!(
+            mime_types.get(path::Path::new("a.cst")),
+            "application/x-custom"
+        );
+        assert_eq!(
+            mime_types.get(path::Path::new("a.cst2")),
+            "application/x-custom"
+        );
+        assert_eq!(mime_types.get(path::Path::new("a.js")), "text/plain");
+    }
+
+    #[test]
+    fn wrong_user_file_line() {
+        let mut mime_types = MimeTypes::default();
+        let result = mime_types.parse("text/plain txt\nhtml\n");
+        assert!(matches!(result, Err(Error::WrongLine(2))));
+    }
+}
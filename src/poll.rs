@@ -0,0 +1,110 @@
+/// Waiting for the incoming connections without busy polling
+use std::{io, time};
+
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, BorrowedFd};
+
+/// How often the sockets are checked on the platforms without poll(2)
+#[cfg(not(unix))]
+const CHECK_INTERVAL: time::Duration = time::Duration::from_millis(10);
+
+/// Sockets that are waited to become readable (to have the pending connections or data) by poll(2)
+#[cfg(unix)]
+pub struct Poll {
+    poll_fds: Vec<libc::pollfd>,
+}
+
+#[cfg(unix)]
+impl Poll {
+    /// The indexes of the sockets are the indexes in the iterator
+    pub fn new<'a>(sockets: impl IntoIterator<Item = BorrowedFd<'a>>) -> Self {
+        let poll_fds = sockets
+            .into_iter()
+            .map(|socket| libc::pollfd {
+                fd: socket.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        Self { poll_fds }
+    }
+
+    /// Blocks until some of the sockets are readable or the timeout expires, None means no timeout.
+    /// Returns the indexes of the readable sockets, it is empty after the timeout or a signal.
+    pub fn wait(&mut self, timeout: Option<time::Duration>) -> Result<Vec<usize>, io::Error> {
+        // The timeout is rounded up, so the caller does not wake up before it and spin
+        let timeout = timeout.map_or(-1, |timeout| {
+            timeout
+                .as_micros()
+                .div_ceil(1000)
+                .min(libc::c_int::MAX as u128) as libc::c_int
+        });
+        // SAFETY: the pointer and the length describe the vector of pollfd that is not used elsewhere during the call.
+        let result = unsafe {
+            libc::poll(
+                self.poll_fds.as_mut_ptr(),
+                self.poll_fds.len() as libc::nfds_t,
+                timeout,
+            )
+        };
+        if result == -1 {
+            let error = io::Error::last_os_error();
+            // Ctrl-C interrupts the call, the caller checks the signal
+            if error.kind() == io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            return Err(error);
+        }
+        Ok(self
+            .poll_fds
+            .iter()
+            .enumerate()
+            .filter(|(_, poll_fd)| poll_fd.revents != 0)
+            .map(|(index, _)| index)
+            .collect())
+    }
+}
+
+/// Fallback for the platforms without poll(2): the non-blocking sockets are checked periodically
+#[cfg(not(unix))]
+pub struct Poll {
+    sockets_number: usize,
+}
+
+#[cfg(not(unix))]
+impl Poll {
+    pub fn new(sockets_number: usize) -> Self {
+        Self { sockets_number }
+    }
+
+    /// Sleeps for the check interval (or the timeout if it is shorter), all sockets are reported as readable
+    pub fn wait(&mut self, timeout: Option<time::Duration>) -> Result<Vec<usize>, io::Error> {
+        std::thread::sleep(timeout.map_or(CHECK_INTERVAL, |timeout| timeout.min(CHECK_INTERVAL)));
+        Ok((0..self.sockets_number).collect())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{io::Write, net, os::fd::AsFd, os::unix::net as unix_net};
+
+    #[test]
+    fn wait_for_readable_sockets() {
+        let tcp_listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let (wake_sender, wake_receiver) = unix_net::UnixStream::pair().unwrap();
+        let mut poll = Poll::new([tcp_listener.as_fd(), wake_receiver.as_fd()]);
+
+        let start = time::Instant::now();
+        let ready = poll.wait(Some(time::Duration::from_millis(50))).unwrap();
+        assert!(ready.is_empty());
+        assert!(start.elapsed() >= time::Duration::from_millis(50));
+
+        let _client = net::TcpStream::connect(tcp_listener.local_addr().unwrap()).unwrap();
+        assert_eq!(poll.wait(None).unwrap(), [0]);
+        (&wake_sender).write_all(&[0]).unwrap();
+        assert_eq!(poll.wait(None).unwrap(), [0, 1]);
+        tcp_listener.accept().unwrap();
+        assert_eq!(poll.wait(None).unwrap(), [1]);
+    }
+}
@@ -0,0 +1,207 @@
+// Byte range requests (RFC 9110, section 14)
+
+/// Maximum number of ranges in one request, longer range sets are ignored and the whole file is sent.
+/// It protects the server from the requests consisting of thousands of tiny ranges.
+const MAX_RANGES_NUMBER: usize = 16;
+
+/// Range of bytes of the representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    /// Position of the first byte
+    pub start: u64,
+    /// Number of bytes, is never zero
+    pub length: u64,
+}
+
+impl ByteRange {
+    /// Position of the last byte
+    pub fn end(&self) -> u64 {
+        self.start + self.length - 1
+    }
+
+    /// Value of the Content-Range header: "bytes 0-499/1234"
+    pub fn content_range(&self, complete_length: u64) -> String {
+        format!("bytes {}-{}/{complete_length}", self.start, self.end())
+    }
+}
+
+/// Result of matching the Range header against the representation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ranges {
+    /// The header is absent, malformed or ignored, the whole representation is sent
+    Full,
+    /// Ranges to send, never empty
+    Satisfiable(Vec<ByteRange>),
+    /// None of the ranges overlaps the representation
+    Unsatisfiable,
+}
+
+/// Parses the value of the Range header ("bytes=0-499, 1000-, -500") for the representation of complete_length bytes.
+/// Unknown range units and syntax errors make the header ignored, as required by RFC 9110.
+/// Overlapping ranges are coalesced.
+pub fn parse(value: &str, complete_length: u64) -> Ranges {
+    let Some((unit, range_set)) = value.split_once('=') else {
+        return Ranges::Full;
+    };
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return Ranges::Full;
+    }
+    let mut ranges = Vec::new();
+    let mut specs_number = 0;
+    for spec in range_set.split(',').map(str::trim) {
+        // Empty list elements are allowed
+        if spec.is_empty() {
+            continue;
+        }
+        specs_number += 1;
+        if specs_number > MAX_RANGES_NUMBER {
+            return Ranges::Full;
+        }
+        let Some((first, last)) = spec.split_once('-') else {
+            return Ranges::Full;
+        };
+        let range = match (parse_position(first), parse_position(last)) {
+            // "-500": last 500 bytes
+            (None, Some(suffix_length)) if first.is_empty() => {
+                if suffix_length == 0 || complete_length == 0 {
+                    continue;
+                }
+                let length = suffix_length.min(complete_length);
+                ByteRange {
+                    start: complete_length - length,
+                    length,
+                }
+            }
+            // "1000-": from 1000 to the end
+            (Some(start), None) if last.is_empty() => {
+                if start >= complete_length {
+                    continue;
+                }
+                ByteRange {
+                    start,
+                    length: complete_length - start,
+                }
+            }
+            // "0-499"
+            (Some(start), Some(end)) if start <= end => {
+                if start >= complete_length {
+                    continue;
+                }
+                ByteRange {
+                    start,
+                    length: end.min(complete_length - 1) - start + 1,
+                }
+            }
+            _ => return Ranges::Full,
+        };
+        ranges.push(range);
+    }
+    if specs_number == 0 {
+        return Ranges::Full;
+    }
+    if ranges.is_empty() {
+        return Ranges::Unsatisfiable;
+    }
+    Ranges::Satisfiable(coalesce(ranges))
+}
+
+/// Parses the byte position, only digits are allowed
+fn parse_position(string: &str) -> Option<u64> {
+    if string.is_empty() || !string.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    string.parse().ok()
+}
+
+/// Merges the overlapping ranges, the order of the ranges is kept if they do not overlap
+fn coalesce(ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    let mut sorted = ranges.clone();
+    sorted.sort_by_key(|range| range.start);
+    let overlaps = sorted.windows(2).any(|pair| pair[1].start <= pair[0].end());
+    if !overlaps {
+        return ranges;
+    }
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(sorted.len());
+    for range in sorted {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end() => {
+                last.length = last.length.max(range.end() - last.start + 1);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, length: u64) -> ByteRange {
+        ByteRange { start, length }
+    }
+
+    #[test]
+    fn single_ranges() {
+        assert_eq!(
+            parse("bytes=0-499", 1000),
+            Ranges::Satisfiable(vec![range(0, 500)])
+        );
+        assert_eq!(
+            parse("bytes=500-", 1000),
+            Ranges::Satisfiable(vec![range(500, 500)])
+        );
+        assert_eq!(
+            parse("bytes=-300", 1000),
+            Ranges::Satisfiable(vec![range(700, 300)])
+        );
+        // The last position and the suffix length are limited by the representation
+        assert_eq!(
+            parse("Bytes = 900-5000", 1000),
+            Ranges::Satisfiable(vec![range(900, 100)])
+        );
+        assert_eq!(
+            parse("bytes=-5000", 1000),
+            Ranges::Satisfiable(vec![range(0, 1000)])
+        );
+        assert_eq!(range(900, 100).content_range(1000), "bytes 900-999/1000");
+    }
+
+    #[test]
+    fn multiple_ranges() {
+        assert_eq!(
+            parse("bytes=500-599, 0-99, -10", 1000),
+            Ranges::Satisfiable(vec![range(500, 100), range(0, 100), range(990, 10)])
+        );
+        // Overlapping and unsatisfiable ranges
+        assert_eq!(
+            parse("bytes=50-150,0-99,2000-,140-160", 1000),
+            Ranges::Satisfiable(vec![range(0, 161)])
+        );
+    }
+
+    #[test]
+    fn unsatisfiable_ranges() {
+        assert_eq!(parse("bytes=1000-", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=1000-2000,-0", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=-10", 0), Ranges::Unsatisfiable);
+    }
+
+    #[test]
+    fn ignored_ranges() {
+        for value in [
+            "",
+            "bytes",
+            "bytes=",
+            "items=0-10",
+            "bytes=10-5",
+            "bytes=a-b",
+            "bytes=+1-2",
+            "bytes=0-1-2",
+            "bytes=-",
+            "bytes=0-0,1-1,2-2,3-3,4-4,5-5,6-6,7-7,8-8,9-9,10-10,11-11,12-12,13-13,14-14,15-15,16-16",
+        ] {
+            assert_eq!(parse(value, 1000), Ranges::Full, "{value}");
+        }
+    }
+}
@@ -0,0 +1,594 @@
+/// HTTP request parsing
+use std::fmt;
+
+/// HTTP request method
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+}
+
+impl Method {
+    /// Parses the method token, methods are case-sensitive.
+    pub fn parse(token: &str) -> Option<Self> {
+        let method = match token {
+            "GET" => Self::Get,
+            "HEAD" => Self::Head,
+            "POST" => Self::Post,
+            "PUT" => Self::Put,
+            "DELETE" => Self::Delete,
+            "CONNECT" => Self::Connect,
+            "OPTIONS" => Self::Options,
+            "TRACE" => Self::Trace,
+            "PATCH" => Self::Patch,
+            _ => return None,
+        };
+        Some(method)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Connect => "CONNECT",
+            Self::Options => "OPTIONS",
+            Self::Trace => "TRACE",
+            Self::Patch => "PATCH",
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// HTTP version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl Version {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http10 => "HTTP/1.0",
+            Self::Http11 => "HTTP/1.1",
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Request header fields.
+/// Names are compared case-insensitively, a field can have several values (several lines with the same name).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    // Pairs of name and value in the order in which they are received
+    fields: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the field value, the previous values of the field are kept
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.fields.push((name.to_string(), value.to_string()));
+    }
+
+    /// Returns the first value of the field
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field_name, _)| field_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns all values of the field
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.fields
+            .iter()
+            .filter(move |(field_name, _)| field_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the elements of a comma-separated list field, which can be split into several lines.
+    /// "Accept-Encoding: gzip, br" for example.
+    pub fn get_list<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.get_all(name)
+            .flat_map(|value| value.split(','))
+            .map(|element| element.trim())
+            .filter(|element| !element.is_empty())
+    }
+
+    /// Returns the elements of a comma-separated list field with their quality values (RFC 9110, section 12.4.2).
+    /// Parameters other than "q" are dropped, elements without "q" have the quality 1.0.
+    /// "Accept-Encoding: gzip;q=0.5, br" gives ("gzip", 0.5) and ("br", 1.0) for example.
+    pub fn get_quality_list<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = (&'a str, f32)> + 'a {
+        self.get_list(name).map(|element| {
+            let mut parameters = element.split(';');
+            let value = parameters.next().unwrap_or_default().trim();
+            let quality = parameters
+                .filter_map(|parameter| parameter.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                .and_then(|(_, quality)| quality.trim().parse::<f32>().ok())
+                .map_or(1.0, |quality| quality.clamp(0.0, 1.0));
+            (value, quality)
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Returns all fields in the order in which they are received
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+/// Query of the request target, "v=3&tag=a%20b" for example.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    // Query as it is received (without '?')
+    raw: String,
+    // Decoded pairs of key and value in the order in which they are received
+    pairs: Vec<(String, String)>,
+}
+
+impl Query {
+    /// Parses the query in the "application/x-www-form-urlencoded" format.
+    /// Keys without '=' have empty values, '+' is decoded as a space.
+    pub fn parse(raw: &str) -> Self {
+        let decode = |string: &str| {
+            let string = string.replace('+', " ");
+            String::from_utf8_lossy(&urlencoding::decode_binary(string.as_bytes())).into_owned()
+        };
+        let pairs = raw
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(key), decode(value))
+            })
+            .collect();
+        Self {
+            raw: raw.to_string(),
+            pairs,
+        }
+    }
+
+    /// Returns the query as it is received
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Returns the first value of the key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(pair_key, _)| pair_key == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns all values of the key
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.pairs
+            .iter()
+            .filter(move |(pair_key, _)| pair_key == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns all pairs of key and value
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+/// HTTP request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: Method,
+    /// Request target as it is received, "/index.html?v=3" for example
+    pub target: String,
+    pub version: Version,
+    pub headers: Headers,
+    /// Percent-encoded path of the target, "/index.html" for example.
+    /// It is "*" for "OPTIONS *" and the authority for CONNECT.
+    pub path: String,
+    /// Query of the target, it is empty if the target has no query
+    pub query: Query,
+}
+
+impl Request {
+    /// Returns the host the request is addressed to.
+    /// The authority of the absolute-form target takes precedence over the Host field (RFC 9112, section 3.2.2).
+    pub fn host(&self) -> Option<&str> {
+        match split_absolute_form(&self.target) {
+            Some((authority, _)) => Some(authority),
+            None => self.headers.get("Host").filter(|host| !host.is_empty()),
+        }
+    }
+}
+
+/// Parses the HTTP request head: the request line and the header fields up to the empty line.
+/// Lines may end with "\r\n" or "\n".
+pub fn parse(head: &str) -> Result<Request, Error> {
+    let mut lines = head.lines();
+    let (method, target, version) = parse_request_line(lines.next().unwrap_or_default())?;
+
+    let mut headers = Headers::new();
+    for line in lines {
+        // lines() removes "\n" and "\r\n"
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = parse_header_line(line)?;
+        headers.append(name, value);
+    }
+
+    // HTTP/1.1 request must contain exactly one Host field (RFC 9112, section 3.2)
+    let hosts_number = headers.get_all("Host").count();
+    if hosts_number > 1 || (version == Version::Http11 && hosts_number == 0) {
+        return Err(Error::WrongHost);
+    }
+
+    let (path, query) = parse_target(method, &target)?;
+    let (path, query) = (path.to_string(), Query::parse(query));
+    Ok(Request {
+        method,
+        target,
+        version,
+        headers,
+        path,
+        query,
+    })
+}
+
+/// Splits the request target into the path and the query (RFC 9112, section 3.2).
+/// The fragment is not a part of the target, but it is removed if the client has sent it.
+fn parse_target(method: Method, target: &str) -> Result<(&str, &str), Error> {
+    // Asterisk-form and authority-form
+    if (method == Method::Options && target == "*") || method == Method::Connect {
+        return Ok((target, ""));
+    }
+    let target = target.split_once('#').map_or(target, |(target, _)| target);
+    // Absolute-form is converted to the origin-form
+    let target = match split_absolute_form(target) {
+        Some((_, "")) => "/",
+        Some((_, path_and_query)) => path_and_query,
+        None => target,
+    };
+    // Origin-form
+    if !target.starts_with('/') {
+        return Err(Error::WrongTarget);
+    }
+    Ok(target.split_once('?').unwrap_or((target, "")))
+}
+
+/// Splits the absolute-form target ("http://host:port/path?query") into the authority and the rest.
+/// Returns None if the target is not in the absolute-form.
+fn split_absolute_form(target: &str) -> Option<(&str, &str)> {
+    let (scheme, rest) = target.split_once("://")?;
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return None;
+    }
+    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (authority, path_and_query) = rest.split_at(authority_end);
+    // Query without the path
+    if path_and_query.starts_with('?') {
+        return None;
+    }
+    Some((authority, path_and_query))
+}
+
+/// Parses the request line: "METHOD TARGET VERSION"
+fn parse_request_line(line: &str) -> Result<(Method, String, Version), Error> {
+    let line = line.trim_end_matches('\n').trim_end_matches('\r');
+    // Exactly three parts separated by single spaces
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(Error::WrongRequestLine);
+    };
+    if method.is_empty() || target.is_empty() || !is_http_version(version) {
+        return Err(Error::WrongRequestLine);
+    }
+    // Method is a token, it can be an extension method unknown to the server
+    if !method.bytes().all(is_token_char) {
+        return Err(Error::WrongRequestLine);
+    }
+    // Target must not contain control characters
+    if target.bytes().any(|byte| byte.is_ascii_control()) {
+        return Err(Error::WrongRequestLine);
+    }
+    let Some(method) = Method::parse(method) else {
+        return Err(Error::UnknownMethod(method.to_string()));
+    };
+    let version = match version {
+        "HTTP/1.0" => Version::Http10,
+        "HTTP/1.1" => Version::Http11,
+        _ => return Err(Error::UnsupportedVersion(version.to_string())),
+    };
+    Ok((method, target.to_string(), version))
+}
+
+/// Parses the header line: "NAME: VALUE"
+fn parse_header_line(line: &str) -> Result<(&str, &str), Error> {
+    let Some((name, value)) = line.split_once(':') else {
+        return Err(Error::WrongHeaderLine(line.to_string()));
+    };
+    // Name can not be empty and can not contain whitespace (including whitespace before ':'),
+    // this also rejects obsolete line folding.
+    if name.is_empty() || !name.bytes().all(is_token_char) {
+        return Err(Error::WrongHeaderLine(line.to_string()));
+    }
+    // Value is surrounded by optional whitespace
+    let value = value.trim_matches(|ch| ch == ' ' || ch == '\t');
+    if value
+        .bytes()
+        .any(|byte| byte.is_ascii_control() && byte != b'\t')
+    {
+        return Err(Error::WrongHeaderLine(line.to_string()));
+    }
+    Ok((name, value))
+}
+
+/// Checks that the string has the "HTTP/x.y" format
+fn is_http_version(version: &str) -> bool {
+    let Some(digits) = version.strip_prefix("HTTP/") else {
+        return false;
+    };
+    let digits = digits.as_bytes();
+    digits.len() == 3
+        && digits[0].is_ascii_digit()
+        && digits[1] == b'.'
+        && digits[2].is_ascii_digit()
+}
+
+/// Checks that the byte can be a part of the token (RFC 9110, section 5.6.2)
+fn is_token_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Wrong request line")]
+    WrongRequestLine,
+    #[error("Unknown method {0}")]
+    UnknownMethod(String),
+    #[error("Unsupported HTTP version {0}")]
+    UnsupportedVersion(String),
+    #[error("Wrong header line \"{0}\"")]
+    WrongHeaderLine(String),
+    #[error("Missing or multiple Host header fields")]
+    WrongHost,
+    #[error("Wrong request target")]
+    WrongTarget,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_standard_methods() {
+        for method in [
+            "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+        ] {
+            let request = parse(&format!("{method} / HTTP/1.0\r\n\r\n")).unwrap();
+            assert_eq!(request.method.as_str(), method);
+        }
+    }
+
+    #[test]
+    fn parse_request() {
+        let request = parse(
+            "GET /index.html?v=3 HTTP/1.1\r\n\
+            Host: localhost:7878\r\n\
+            accept-encoding: gzip,  br\r\n\
+            Accept-Encoding: zstd\r\n\
+            If-None-Match:\t\"abc\"  \r\n\
+            \r\n",
+        )
+        .unwrap();
+        assert_eq!(request.method, Method::Get);
+        assert_eq!(request.target, "/index.html?v=3");
+        assert_eq!(request.version, Version::Http11);
+        assert_eq!(request.path, "/index.html");
+        assert_eq!(request.query.get("v"), Some("3"));
+        assert_eq!(request.host(), Some("localhost:7878"));
+        assert_eq!(request.headers.get("HOST"), Some("localhost:7878"));
+        assert_eq!(request.headers.get("if-none-match"), Some("\"abc\""));
+        assert_eq!(
+            request
+                .headers
+                .get_all("Accept-Encoding")
+                .collect::<Vec<_>>(),
+            ["gzip,  br", "zstd"]
+        );
+        assert_eq!(
+            request
+                .headers
+                .get_list("Accept-Encoding")
+                .collect::<Vec<_>>(),
+            ["gzip", "br", "zstd"]
+        );
+        assert_eq!(
+            request
+                .headers
+                .get_quality_list("Accept-Encoding")
+                .collect::<Vec<_>>(),
+            [("gzip", 1.0), ("br", 1.0), ("zstd", 1.0)]
+        );
+        assert!(!request.headers.contains("Range"));
+    }
+
+    #[test]
+    fn parse_request_with_lf_line_endings() {
+        let request = parse("HEAD / HTTP/1.0\nAccept: */*\n\n").unwrap();
+        assert_eq!(request.method, Method::Head);
+        assert_eq!(request.version, Version::Http10);
+        assert!(request.query.is_empty());
+        assert_eq!(request.headers.get("Accept"), Some("*/*"));
+    }
+
+    #[test]
+    fn parse_quality_list() {
+        let request = parse(
+            "GET / HTTP/1.0\r\n\
+            Accept: text/html;level=1;q=0.5, application/json ; Q=0.9,*/*;q=2\r\n\
+            \r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            request
+                .headers
+                .get_quality_list("Accept")
+                .collect::<Vec<_>>(),
+            [("text/html", 0.5), ("application/json", 0.9), ("*/*", 1.0)]
+        );
+    }
+
+    #[test]
+    fn parse_query() {
+        let query = Query::parse("a=1&b=x%20y+z&flag&&a=2&%F0%9F%A6%80=%ZZ");
+        assert_eq!(query.as_str(), "a=1&b=x%20y+z&flag&&a=2&%F0%9F%A6%80=%ZZ");
+        assert_eq!(query.get("a"), Some("1"));
+        assert_eq!(query.get_all("a").collect::<Vec<_>>(), ["1", "2"]);
+        assert_eq!(query.get("b"), Some("x y z"));
+        assert_eq!(query.get("flag"), Some(""));
+        assert_eq!(query.get("\u{1F980}"), Some("%ZZ"));
+        assert_eq!(query.get("c"), None);
+        assert_eq!(query.iter().count(), 5);
+    }
+
+    #[test]
+    fn parse_target_forms() {
+        let request = parse("GET /a/b.html?x=1#top HTTP/1.0\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/a/b.html");
+        assert_eq!(request.query.as_str(), "x=1");
+
+        let request = parse("GET /a/b.html#top?x=1 HTTP/1.0\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/a/b.html");
+        assert!(request.query.is_empty());
+
+        let request =
+            parse("GET http://example.com:8080/a.css?v=2 HTTP/1.1\r\nHost: other\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/a.css");
+        assert_eq!(request.query.get("v"), Some("2"));
+        assert_eq!(request.host(), Some("example.com:8080"));
+
+        let request = parse("GET HTTPS://example.com HTTP/1.1\r\nHost: a\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/");
+
+        let request = parse("OPTIONS * HTTP/1.1\r\nHost: a\r\n\r\n").unwrap();
+        assert_eq!(request.path, "*");
+
+        let targets = [
+            "*",
+            "index.html",
+            "ftp://example.com/a",
+            "http://example.com?a",
+        ];
+        for target in targets {
+            let result = parse(&format!("GET {target} HTTP/1.0\r\n\r\n"));
+            assert!(matches!(result, Err(Error::WrongTarget)), "{target}");
+        }
+    }
+
+    #[test]
+    fn parse_unknown_method() {
+        let result = parse("BREW /pot HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert!(matches!(result, Err(Error::UnknownMethod(method)) if method == "BREW"));
+        // Methods are case-sensitive
+        let result = parse("get / HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert!(matches!(result, Err(Error::UnknownMethod(_))));
+    }
+
+    #[test]
+    fn parse_unsupported_version() {
+        let result = parse("GET / HTTP/2.0\r\n\r\n");
+        assert!(matches!(result, Err(Error::UnsupportedVersion(version)) if version == "HTTP/2.0"));
+    }
+
+    #[test]
+    fn parse_wrong_request_lines() {
+        let lines = [
+            "",
+            "GET\r\n",
+            "GET /\r\n",
+            "GET  / HTTP/1.1\r\n",
+            "GET / HTTP/1.1 extra\r\n",
+            "GET / HTTP/11\r\n",
+            "GET / FTP/1.1\r\n",
+            "G(T / HTTP/1.1\r\n",
+            "GET /\x7f HTTP/1.1\r\n",
+        ];
+        for line in lines {
+            assert!(
+                matches!(parse(line), Err(Error::WrongRequestLine)),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_wrong_header_lines() {
+        let header_lines = [
+            "Host localhost",
+            "Host : localhost",
+            ": localhost",
+            " folded: value",
+            "Bad\x01Name: value",
+            "Name: bad\x01value",
+        ];
+        for header_line in header_lines {
+            let result = parse(&format!("GET / HTTP/1.0\r\n{header_line}\r\n\r\n"));
+            assert!(
+                matches!(result, Err(Error::WrongHeaderLine(_))),
+                "{header_line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_wrong_host() {
+        let result = parse("GET / HTTP/1.1\r\n\r\n");
+        assert!(matches!(result, Err(Error::WrongHost)));
+        let result = parse("GET / HTTP/1.1\r\nHost: a\r\nHost: b\r\n\r\n");
+        assert!(matches!(result, Err(Error::WrongHost)));
+        // Host is optional in HTTP/1.0
+        assert!(parse("GET / HTTP/1.0\r\n\r\n").is_ok());
+    }
+}
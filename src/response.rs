@@ -0,0 +1,726 @@
+/// HTTP response forming
+use crate::{compression, date_time, range};
+use std::{fmt::Write as _, fs, io, io::Read, io::Seek, io::Write, mem, net, time};
+
+#[cfg(target_os = "linux")]
+use crate::sendfile;
+
+/// Size of the chunks in which the file body is sent
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// HTTP response status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    PartialContent,
+    MovedPermanently,
+    NotModified,
+    PermanentRedirect,
+    BadRequest,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    PreconditionFailed,
+    UriTooLong,
+    RangeNotSatisfiable,
+    RequestHeaderFieldsTooLarge,
+    InternalServerError,
+    NotImplemented,
+    HttpVersionNotSupported,
+}
+
+impl Status {
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::Ok => 200,
+            Self::PartialContent => 206,
+            Self::MovedPermanently => 301,
+            Self::NotModified => 304,
+            Self::PermanentRedirect => 308,
+            Self::BadRequest => 400,
+            Self::Forbidden => 403,
+            Self::NotFound => 404,
+            Self::MethodNotAllowed => 405,
+            Self::PreconditionFailed => 412,
+            Self::UriTooLong => 414,
+            Self::RangeNotSatisfiable => 416,
+            Self::RequestHeaderFieldsTooLarge => 431,
+            Self::InternalServerError => 500,
+            Self::NotImplemented => 501,
+            Self::HttpVersionNotSupported => 505,
+        }
+    }
+
+    pub fn reason_phrase(&self) -> &'static str {
+        match self {
+            Self::Ok => "OK",
+            Self::PartialContent => "Partial Content",
+            Self::MovedPermanently => "Moved Permanently",
+            Self::NotModified => "Not Modified",
+            Self::PermanentRedirect => "Permanent Redirect",
+            Self::BadRequest => "Bad Request",
+            Self::Forbidden => "Forbidden",
+            Self::NotFound => "Not Found",
+            Self::MethodNotAllowed => "Method Not Allowed",
+            Self::PreconditionFailed => "Precondition Failed",
+            Self::UriTooLong => "URI Too Long",
+            Self::RangeNotSatisfiable => "Range Not Satisfiable",
+            Self::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
+            Self::InternalServerError => "Internal Server Error",
+            Self::NotImplemented => "Not Implemented",
+            Self::HttpVersionNotSupported => "HTTP Version Not Supported",
+        }
+    }
+}
+
+/// Body of the HTTP response
+pub enum Body {
+    /// Body stored in memory
+    Bytes(Vec<u8>),
+    /// Body read from the file, only length bytes from the current position are sent
+    File { file: fs::File, length: u64 },
+    /// Several parts of the file, each one preceded by its own head, and the final bytes (multipart/byteranges)
+    FileParts {
+        file: fs::File,
+        parts: Vec<FilePart>,
+        end: Vec<u8>,
+    },
+    /// Body read from the file (length bytes from the current position) and compressed while it is sent.
+    /// Its length is unknown in advance, so it is sent with the chunked transfer coding.
+    EncodedFile {
+        file: fs::File,
+        length: u64,
+        encoding: compression::Encoding,
+    },
+}
+
+/// Part of the multipart body: the part head and the range of the file
+pub struct FilePart {
+    pub head: Vec<u8>,
+    pub range: range::ByteRange,
+}
+
+impl Body {
+    /// Returns the length of the body, None if it is unknown before the body is sent
+    pub fn len(&self) -> Option<u64> {
+        match self {
+            Self::Bytes(bytes) => Some(bytes.len() as u64),
+            Self::File { length, .. } => Some(*length),
+            Self::FileParts { parts, end, .. } => Some(
+                parts
+                    .iter()
+                    .map(|part| part.head.len() as u64 + part.range.length)
+                    .sum::<u64>()
+                    + end.len() as u64,
+            ),
+            Self::EncodedFile { .. } => None,
+        }
+    }
+}
+
+/// HTTP response
+pub struct Response {
+    status: Status,
+    // Headers except Server, Connection and Content-Length, they are added when the response is formed
+    headers: Vec<(String, String)>,
+    body: Body,
+    // Should the connection be kept open after the response
+    keep_alive: bool,
+    // Is the body the built-in page describing the status, it can be replaced by the custom page
+    is_error_page: bool,
+}
+
+impl Response {
+    /// Creates a response without headers and body
+    pub fn new(status: Status) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Body::Bytes(Vec::new()),
+            keep_alive: false,
+            is_error_page: false,
+        }
+    }
+
+    /// Creates a response with the body
+    pub fn with_body(status: Status, content_type: &str, body: Vec<u8>) -> Self {
+        let mut response = Self::new(status);
+        response.add_header("Content-Type", content_type);
+        response.body = Body::Bytes(body);
+        response
+    }
+
+    /// Creates a response whose body is streamed from the file.
+    /// The body is length bytes from the current position of the file.
+    pub fn with_file(status: Status, content_type: &str, file: fs::File, length: u64) -> Self {
+        let mut response = Self::new(status);
+        response.add_header("Content-Type", content_type);
+        response.body = Body::File { file, length };
+        response
+    }
+
+    /// Creates the 206 response containing several ranges of the file as multipart/byteranges body.
+    /// The file position does not matter, every part is read from its own offset.
+    pub fn with_file_ranges(
+        content_type: &str,
+        file: fs::File,
+        ranges: &[range::ByteRange],
+        complete_length: u64,
+    ) -> Self {
+        // The boundary must not appear in the file, the time in nanoseconds makes it unlikely
+        let boundary = format!(
+            "SimpleWebServer{:x}",
+            time::SystemTime::now()
+                .duration_since(time::UNIX_EPOCH)
+                .map(|duration| duration.as_nanos())
+                .unwrap_or(0)
+        );
+        let parts = ranges
+            .iter()
+            .enumerate()
+            .map(|(index, range)| {
+                // The delimiter of every part except the first one starts with CRLF ending the previous part
+                let head = format!(
+                    "{}--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: {}\r\n\r\n",
+                    if index == 0 { "" } else { "\r\n" },
+                    range.content_range(complete_length)
+                );
+                FilePart {
+                    head: head.into_bytes(),
+                    range: *range,
+                }
+            })
+            .collect();
+        let end = format!("\r\n--{boundary}--\r\n").into_bytes();
+
+        let mut response = Self::new(Status::PartialContent);
+        response.add_header(
+            "Content-Type",
+            &format!("multipart/byteranges; boundary={boundary}"),
+        );
+        response.body = Body::FileParts { file, parts, end };
+        response
+    }
+
+    /// Creates a response with the built-in HTML page describing the status
+    pub fn error_page(status: Status) -> Self {
+        let page = ERROR_HTML_PAGE_TEMPLATE
+            .replace("{code}", &status.code().to_string())
+            .replace("{reason}", status.reason_phrase());
+        let mut response = Self::with_body(status, HTML_CONTENT_TYPE, page.into_bytes());
+        response.is_error_page = true;
+        response
+    }
+
+    /// Checks if the body is the built-in page created by error_page
+    pub fn is_error_page(&self) -> bool {
+        self.is_error_page
+    }
+
+    /// Replaces the body by length bytes from the current position of the file, other headers are kept
+    pub fn set_file_body(&mut self, content_type: &str, file: fs::File, length: u64) {
+        self.headers
+            .retain(|(name, _)| !name.eq_ignore_ascii_case("Content-Type"));
+        self.add_header("Content-Type", content_type);
+        self.body = Body::File { file, length };
+        self.is_error_page = false;
+    }
+
+    /// Compresses the body with the encoding and adds the Content-Encoding header.
+    /// The body in memory is compressed at once. The file body is compressed while it is sent if chunked is true
+    /// (the client supports the chunked transfer coding), otherwise it is compressed into memory.
+    /// Multipart bodies are left as is.
+    pub fn encode(
+        &mut self,
+        encoding: compression::Encoding,
+        chunked: bool,
+    ) -> Result<(), io::Error> {
+        self.body = match mem::replace(&mut self.body, Body::Bytes(Vec::new())) {
+            Body::Bytes(bytes) => Body::Bytes(compression::encode(encoding, &bytes)?),
+            Body::File { file, length } if chunked => Body::EncodedFile {
+                file,
+                length,
+                encoding,
+            },
+            Body::File { file, length } => {
+                let mut encoder = compression::Encoder::new(encoding, Vec::new())?;
+                copy_file(&file, length, &mut encoder)?;
+                Body::Bytes(encoder.finish()?)
+            }
+            body => {
+                self.body = body;
+                return Ok(());
+            }
+        };
+        self.add_header("Content-Encoding", encoding.as_str());
+        Ok(())
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Adds the Cache-Control header and, if max_age (in seconds) is known, the Expires header for HTTP/1.0 caches.
+    /// Expires is omitted if the time is not representable.
+    pub fn add_cache_control(&mut self, cache_control: &str, max_age: Option<u64>) {
+        self.add_header("Cache-Control", cache_control);
+        let expires = max_age.and_then(|max_age| {
+            time::SystemTime::now().checked_add(time::Duration::from_secs(max_age))
+        });
+        if let Some(expires) = expires {
+            let expires = date_time::DateTime::from_system_time(expires);
+            self.add_header("Expires", &expires.to_http_date());
+        }
+    }
+
+    pub fn set_keep_alive(&mut self, keep_alive: bool) {
+        self.keep_alive = keep_alive;
+    }
+
+    pub fn add_header(&mut self, name: &str, value: &str) {
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+
+    /// Forms the response head: the status line and the header fields with the final empty line
+    fn head(&self) -> String {
+        let mut head = String::new();
+        // Adds first line
+        let _ = write!(
+            &mut head,
+            "HTTP/1.1 {} {}\r\n",
+            self.status.code(),
+            self.status.reason_phrase()
+        );
+        // Adds Server header
+        head.push_str("Server: Simple Web Server\r\n");
+        // Adds Connection header
+        if self.keep_alive {
+            head.push_str("Connection: keep-alive\r\n");
+        } else {
+            head.push_str("Connection: close\r\n");
+        }
+        // Adds other headers
+        for (name, value) in &self.headers {
+            let _ = write!(&mut head, "{name}: {value}\r\n");
+        }
+        // Adds Content-Length header, 304 response has no body and Content-Length would describe the omitted file
+        if self.status != Status::NotModified {
+            match self.body.len() {
+                Some(length) => {
+                    let _ = write!(&mut head, "Content-Length: {length}\r\n");
+                }
+                None => head.push_str("Transfer-Encoding: chunked\r\n"),
+            }
+        }
+        // Adds empty line
+        head.push_str("\r\n");
+        head
+    }
+
+    /// Writes the response.
+    /// If include_body is false (answer to HEAD), the body is omitted, but Content-Length still describes it.
+    /// The file body is sent in chunks of FILE_CHUNK_SIZE bytes, so the memory usage does not depend on the file size.
+    pub fn write_to(&self, mut writer: impl Write, include_body: bool) -> Result<(), io::Error> {
+        writer.write_all(self.head().as_bytes())?;
+        if include_body {
+            match &self.body {
+                Body::Bytes(bytes) => writer.write_all(bytes)?,
+                Body::File { file, length } => copy_file(file, *length, &mut writer)?,
+                Body::FileParts { file, parts, end } => {
+                    for part in parts {
+                        writer.write_all(&part.head)?;
+                        (&*file).seek(io::SeekFrom::Start(part.range.start))?;
+                        copy_file(file, part.range.length, &mut writer)?;
+                    }
+                    writer.write_all(end)?;
+                }
+                Body::EncodedFile {
+                    file,
+                    length,
+                    encoding,
+                } => {
+                    // The compressed data is collected into chunks of FILE_CHUNK_SIZE bytes
+                    let chunked_writer = io::BufWriter::with_capacity(
+                        FILE_CHUNK_SIZE,
+                        ChunkedWriter::new(&mut writer),
+                    );
+                    let mut encoder = compression::Encoder::new(*encoding, chunked_writer)?;
+                    copy_file(file, *length, &mut encoder)?;
+                    encoder
+                        .finish()?
+                        .into_inner()
+                        .map_err(io::IntoInnerError::into_error)?
+                        .finish()?;
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    /// Writes the response to the socket.
+    /// If use_sendfile is true, on Linux the file body is sent by sendfile(2) directly from the page cache,
+    /// otherwise (and on other platforms) it is copied through the userspace buffer.
+    pub fn write_to_socket<S: Socket>(
+        &self,
+        socket: &S,
+        include_body: bool,
+        use_sendfile: bool,
+    ) -> Result<(), io::Error>
+    where
+        for<'a> &'a S: Write,
+    {
+        #[cfg(target_os = "linux")]
+        if include_body && use_sendfile {
+            match &self.body {
+                Body::File { file, length } => {
+                    write_flushed(socket, self.head().as_bytes())?;
+                    return send_file(file, *length, socket);
+                }
+                Body::FileParts { file, parts, end } => {
+                    write_flushed(socket, self.head().as_bytes())?;
+                    for part in parts {
+                        write_flushed(socket, &part.head)?;
+                        (&*file).seek(io::SeekFrom::Start(part.range.start))?;
+                        send_file(file, part.range.length, socket)?;
+                    }
+                    return write_flushed(socket, end);
+                }
+                // The compressed data can not be sent directly from the page cache
+                Body::Bytes(_) | Body::EncodedFile { .. } => {}
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = use_sendfile;
+        self.write_to(io::BufWriter::new(socket), include_body)
+    }
+}
+
+/// Connected socket (TCP or Unix domain) the file bodies can be sent to by sendfile(2) on Linux
+#[cfg(target_os = "linux")]
+pub trait Socket: std::os::fd::AsRawFd {}
+#[cfg(not(target_os = "linux"))]
+pub trait Socket {}
+
+impl Socket for net::TcpStream {}
+#[cfg(unix)]
+impl Socket for std::os::unix::net::UnixStream {}
+
+/// Writer of the chunked transfer coding (RFC 9112, section 7.1), every write forms one chunk
+struct ChunkedWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes the last chunk, the trailer section is always empty
+    fn finish(mut self) -> Result<W, io::Error> {
+        self.writer.write_all(b"0\r\n\r\n")?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Empty chunk would be the last one
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.writer, "{:x}\r\n", buf.len())?;
+        self.writer.write_all(buf)?;
+        self.writer.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes the bytes to the socket before the file data is sent directly to it
+#[cfg(target_os = "linux")]
+fn write_flushed<S: Socket>(socket: &S, bytes: &[u8]) -> Result<(), io::Error>
+where
+    for<'a> &'a S: Write,
+{
+    let mut writer = io::BufWriter::new(socket);
+    writer.write_all(bytes)?;
+    writer.flush()
+}
+
+/// Sends length bytes from the current position of the file to the socket by sendfile(2),
+/// the file is copied through the userspace buffer if sendfile is not supported
+#[cfg(target_os = "linux")]
+fn send_file<S: Socket>(file: &fs::File, length: u64, socket: &S) -> Result<(), io::Error>
+where
+    for<'a> &'a S: Write,
+{
+    if sendfile::send_file(file, length, socket)? {
+        return Ok(());
+    }
+    // sendfile is not supported, nothing has been sent yet
+    let mut writer = io::BufWriter::new(socket);
+    copy_file(file, length, &mut writer)?;
+    writer.flush()
+}
+
+/// Copies length bytes from the current position of the file to the writer
+fn copy_file(file: &fs::File, length: u64, writer: &mut impl Write) -> Result<(), io::Error> {
+    let mut buffer = vec![0; FILE_CHUNK_SIZE.min(length as usize)];
+    let mut remaining = length;
+    let mut file = file.take(length);
+    while remaining > 0 {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            // The file has been truncated after Content-Length was sent, the answer can't be completed
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file is shorter than Content-Length",
+            ));
+        }
+        writer.write_all(&buffer[..read])?;
+        remaining -= read as u64;
+    }
+    Ok(())
+}
+
+/// Content type of the built-in pages
+const HTML_CONTENT_TYPE: &str = "text/html; charset=utf-8";
+
+static ERROR_HTML_PAGE_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <title>Simple Web Server</title>
+    <style>
+        body {
+            font-family: Arial, sans-serif;
+            background-color: #f0f0f0;
+            margin: 0;
+            padding: 0;
+            display: flex;
+            justify-content: center;
+            align-items: center;
+            height: 100vh;
+            flex-direction: column;
+        }
+        h1 {
+            color: #333;
+        }
+        p {
+            color: #666;
+            text-align: center;
+            max-width: 600px;
+            margin: 0;
+            font-size: 1.2em;
+        }
+    </style>
+</head>
+<body>
+    <h1>{code}</h1>
+    <p>{reason}</p>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_bytes(response: &Response, include_body: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        response.write_to(&mut bytes, include_body).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn response_keeps_binary_body() {
+        let response = Response::with_body(Status::Ok, "image/png", vec![0x89, 0x50, 0xFF, 0x00]);
+        let bytes = to_bytes(&response, true);
+        assert!(bytes.starts_with(b"HTTP/1.1 200 OK\r\n"));
+        assert!(bytes.ends_with(b"Content-Length: 4\r\n\r\n\x89\x50\xFF\x00"));
+    }
+
+    #[test]
+    fn response_without_body() {
+        let response = Response::with_body(Status::Ok, "text/plain", b"content".to_vec());
+        let bytes = to_bytes(&response, false);
+        assert!(bytes.ends_with(b"Content-Length: 7\r\n\r\n"));
+    }
+
+    #[test]
+    fn response_with_file_body() {
+        let path = std::env::temp_dir().join("simple_web_server_response_file.bin");
+        let content: Vec<u8> = (0..FILE_CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        fs::write(&path, &content).unwrap();
+        let file = fs::File::open(&path).unwrap();
+        let response = Response::with_file(
+            Status::Ok,
+            "application/octet-stream",
+            file,
+            content.len() as u64,
+        );
+        let bytes = to_bytes(&response, true);
+        // The file is shorter than the length
+        let file = fs::File::open(&path).unwrap();
+        let truncated = Response::with_file(
+            Status::Ok,
+            "application/octet-stream",
+            file,
+            content.len() as u64 + 1,
+        );
+        let result = truncated.write_to(io::sink(), true);
+        let _ = fs::remove_file(&path);
+
+        let head = format!("Content-Length: {}\r\n\r\n", content.len());
+        let body_start = bytes.len() - content.len();
+        assert!(bytes[..body_start].ends_with(head.as_bytes()));
+        assert_eq!(&bytes[body_start..], &content);
+        assert!(matches!(result, Err(error) if error.kind() == io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn response_with_file_ranges() {
+        let path = std::env::temp_dir().join("simple_web_server_response_file_ranges.txt");
+        fs::write(&path, "0123456789").unwrap();
+        let file = fs::File::open(&path).unwrap();
+        let ranges = [
+            range::ByteRange {
+                start: 7,
+                length: 3,
+            },
+            range::ByteRange {
+                start: 0,
+                length: 2,
+            },
+        ];
+        let response = Response::with_file_ranges("text/plain", file, &ranges, 10);
+        let bytes = String::from_utf8(to_bytes(&response, true)).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let boundary = bytes
+            .split("boundary=")
+            .nth(1)
+            .and_then(|rest| rest.split("\r\n").next())
+            .unwrap();
+        let body = format!(
+            "--{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 7-9/10\r\n\r\n789\r\n\
+            --{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/10\r\n\r\n01\r\n\
+            --{boundary}--\r\n"
+        );
+        assert!(bytes.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(bytes.ends_with(&format!("Content-Length: {}\r\n\r\n{body}", body.len())));
+    }
+
+    #[test]
+    fn response_with_encoded_file_body() {
+        let path = std::env::temp_dir().join("simple_web_server_response_encoded_file.txt");
+        let content = "Simple Web Server ".repeat(10000);
+        fs::write(&path, &content).unwrap();
+        let encoded_response = |chunked| {
+            let file = fs::File::open(&path).unwrap();
+            let mut response =
+                Response::with_file(Status::Ok, "text/plain", file, content.len() as u64);
+            response
+                .encode(compression::Encoding::Gzip, chunked)
+                .unwrap();
+            let bytes = to_bytes(&response, true);
+            let body_start = bytes.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+            let head = String::from_utf8(bytes[..body_start].to_vec()).unwrap();
+            (head, bytes[body_start..].to_vec())
+        };
+        let (chunked_head, chunked_body) = encoded_response(true);
+        let (buffered_head, buffered_body) = encoded_response(false);
+        let _ = fs::remove_file(&path);
+        let decode = |body: &[u8]| {
+            let mut decoded = String::new();
+            flate2::read::GzDecoder::new(body)
+                .read_to_string(&mut decoded)
+                .unwrap();
+            decoded
+        };
+
+        assert!(chunked_head.contains("Content-Encoding: gzip\r\n"));
+        assert!(chunked_head.ends_with("Transfer-Encoding: chunked\r\n\r\n"));
+        // Chunks are joined back
+        let mut rest = chunked_body.as_slice();
+        let mut joined = Vec::new();
+        loop {
+            let line_end = rest.windows(2).position(|w| w == b"\r\n").unwrap();
+            let size = std::str::from_utf8(&rest[..line_end]).unwrap();
+            let size = usize::from_str_radix(size, 16).unwrap();
+            rest = &rest[line_end + 2..];
+            if size == 0 {
+                assert_eq!(rest, b"\r\n");
+                break;
+            }
+            joined.extend_from_slice(&rest[..size]);
+            assert_eq!(&rest[size..size + 2], b"\r\n");
+            rest = &rest[size + 2..];
+        }
+        assert_eq!(decode(&joined), content);
+
+        assert!(
+            buffered_head.ends_with(&format!("Content-Length: {}\r\n\r\n", buffered_body.len()))
+        );
+        assert_eq!(decode(&buffered_body), content);
+    }
+
+    #[test]
+    fn cache_control_headers() {
+        let mut response = Response::new(Status::Ok);
+        response.add_cache_control("no-cache", None);
+        let bytes = String::from_utf8(to_bytes(&response, true)).unwrap();
+        assert!(bytes.contains("\r\nCache-Control: no-cache\r\n"));
+        assert!(!bytes.contains("Expires"));
+
+        let mut response = Response::new(Status::Ok);
+        response.add_cache_control("max-age=3600", Some(3600));
+        let bytes = String::from_utf8(to_bytes(&response, true)).unwrap();
+        let expires = bytes
+            .split("\r\nExpires: ")
+            .nth(1)
+            .and_then(|rest| rest.split("\r\n").next())
+            .and_then(date_time::DateTime::parse_http_date)
+            .unwrap();
+        let now = date_time::DateTime::from_system_time(time::SystemTime::now());
+        assert!((3599..=3601).contains(&(expires.to_unix_seconds() - now.to_unix_seconds())));
+
+        // The time that overflows SystemTime is not sent
+        let mut response = Response::new(Status::Ok);
+        response.add_cache_control("max-age=18446744073709551615", Some(u64::MAX));
+        let bytes = String::from_utf8(to_bytes(&response, true)).unwrap();
+        assert!(bytes.contains("\r\nCache-Control: max-age=18446744073709551615\r\n"));
+        assert!(!bytes.contains("Expires"));
+    }
+
+    #[test]
+    fn error_page_with_file_body() {
+        let path = std::env::temp_dir().join("simple_web_server_response_error_page.html");
+        fs::write(&path, "<h1>Custom</h1>").unwrap();
+        let mut response = Response::error_page(Status::MethodNotAllowed);
+        response.add_header("Allow", "GET");
+        assert!(response.is_error_page());
+        response.set_file_body("text/html", fs::File::open(&path).unwrap(), 15);
+        let bytes = String::from_utf8(to_bytes(&response, true)).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert!(!response.is_error_page());
+        assert!(bytes.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(bytes.contains("\r\nAllow: GET\r\n"));
+        assert_eq!(bytes.matches("Content-Type").count(), 1);
+        assert!(bytes.contains("\r\nContent-Type: text/html\r\n"));
+        assert!(bytes.ends_with("Content-Length: 15\r\n\r\n<h1>Custom</h1>"));
+    }
+
+    #[test]
+    fn error_page_contains_status() {
+        let response = Response::error_page(Status::NotFound);
+        let bytes = String::from_utf8(to_bytes(&response, true)).unwrap();
+        assert!(bytes.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(bytes.contains("<h1>404</h1>"));
+        assert!(bytes.contains("<p>Not Found</p>"));
+    }
+}
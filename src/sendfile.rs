@@ -0,0 +1,78 @@
+/// Zero-copy file transmission with sendfile(2) on Linux
+use std::{fs, io, os::fd::AsRawFd};
+
+/// Maximum number of bytes transferred by one sendfile call (the limit of the Linux kernel)
+const MAX_SENDFILE_COUNT: u64 = 0x7fff_f000;
+
+/// Sends length bytes from the current position of the file to the socket.
+/// The data goes from the page cache directly to the socket, without copying through userspace buffers.
+/// Returns Ok(false) if sendfile is not supported for this file and nothing has been sent,
+/// in this case the caller should copy the file in the usual way.
+pub fn send_file(file: &fs::File, length: u64, socket: &impl AsRawFd) -> Result<bool, io::Error> {
+    let mut remaining = length;
+    while remaining > 0 {
+        let count = remaining.min(MAX_SENDFILE_COUNT) as usize;
+        // Null offset means that the current position of the file is used and updated
+        // SAFETY: both descriptors are valid for the duration of the call, the offset pointer is null.
+        let sent = unsafe {
+            libc::sendfile(
+                socket.as_raw_fd(),
+                file.as_raw_fd(),
+                std::ptr::null_mut(),
+                count,
+            )
+        };
+        if sent < 0 {
+            let error = io::Error::last_os_error();
+            match error.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                // The file or the socket does not support sendfile
+                Some(libc::EINVAL) | Some(libc::ENOSYS) if remaining == length => return Ok(false),
+                _ => return Err(error),
+            }
+        }
+        if sent == 0 {
+            // The file has been truncated after Content-Length was sent, the answer can't be completed
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file is shorter than Content-Length",
+            ));
+        }
+        remaining -= sent as u64;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Read, net, thread};
+
+    #[test]
+    fn send_file_to_socket() {
+        let path = std::env::temp_dir().join("simple_web_server_sendfile.bin");
+        let content: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &content).unwrap();
+        let file = fs::File::open(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let reader = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).unwrap();
+            received
+        });
+        let socket = net::TcpStream::connect(addr).unwrap();
+        // Skip the first 10 bytes, the current position is used
+        io::Seek::seek(&mut &file, io::SeekFrom::Start(10)).unwrap();
+        assert!(send_file(&file, content.len() as u64 - 10, &socket).unwrap());
+        // Nothing is left in the file
+        let result = send_file(&file, 1, &socket);
+        drop(socket);
+
+        assert!(matches!(result, Err(error) if error.kind() == io::ErrorKind::UnexpectedEof));
+        assert_eq!(reader.join().unwrap(), &content[10..]);
+    }
+}
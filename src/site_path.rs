@@ -0,0 +1,295 @@
+/// Mapping of the requested paths to the site files.
+/// Makes sure that the requests can not escape the root folder.
+use std::{fs, io, path, string};
+
+/// Normalizes the percent-encoded path from the HTTP request into a path relative to the root folder.
+/// "." segments and empty segments are skipped, ".." segments remove the previous segment.
+/// Paths that try to go above the root, contain NUL bytes or encoded separators are rejected.
+pub fn normalize(request_path: &str) -> Result<path::PathBuf, Error> {
+    // Path in the request must be absolute ("/...")
+    let Some(request_path) = request_path.strip_prefix('/') else {
+        return Err(Error::NotAbsolute);
+    };
+    let mut segments: Vec<String> = Vec::new();
+    // Segments are decoded separately, so an encoded '/' can not create a new segment
+    for raw_segment in request_path.split('/') {
+        let segment = urlencoding::decode(raw_segment)?;
+        if segment.contains('\0') {
+            return Err(Error::NulByte);
+        }
+        // '\' is a separator on Windows
+        if segment.contains('/') || segment.contains('\\') {
+            return Err(Error::EncodedSeparator);
+        }
+        match segment.as_ref() {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(Error::OutsideRoot);
+                }
+            }
+            segment => segments.push(segment.to_string()),
+        }
+    }
+    Ok(segments.iter().collect())
+}
+
+/// Joins the normalized relative path with the canonical root folder path and checks that
+/// the final file (after resolving symbolic links) is located inside the root folder.
+/// Returns Ok(None) if the file does not exist.
+pub fn resolve(
+    canonical_root_folder_path: &path::Path,
+    relative_path: &path::Path,
+) -> Result<Option<path::PathBuf>, Error> {
+    let full_path = canonical_root_folder_path.join(relative_path);
+    let canonical_path = match full_path.canonicalize() {
+        Ok(canonical_path) => canonical_path,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(Error::CanonicalizationFailed(error)),
+    };
+    if !canonical_path.starts_with(canonical_root_folder_path) {
+        return Err(Error::OutsideRoot);
+    }
+    Ok(Some(canonical_path))
+}
+
+/// Finds the first existing index file in the folder.
+/// Index files are checked in the order of index_file_names, only regular files inside the root folder are taken.
+pub fn find_index(
+    canonical_root_folder_path: &path::Path,
+    relative_folder_path: &path::Path,
+    index_file_names: &[String],
+) -> Result<Option<path::PathBuf>, Error> {
+    for index_file_name in index_file_names {
+        let relative_path = relative_folder_path.join(index_file_name);
+        if let Some(index_path) = resolve(canonical_root_folder_path, &relative_path)? {
+            if index_path.is_file() {
+                return Ok(Some(index_path));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Finds the sibling of the file with the extension added to its name ("app.js.br" for "app.js").
+/// Only regular files inside the root folder are taken.
+pub fn find_sibling(
+    canonical_root_folder_path: &path::Path,
+    relative_file_path: &path::Path,
+    extension: &str,
+) -> Result<Option<path::PathBuf>, Error> {
+    let mut sibling_name = relative_file_path.as_os_str().to_owned();
+    sibling_name.push(".");
+    sibling_name.push(extension);
+    match resolve(canonical_root_folder_path, path::Path::new(&sibling_name))? {
+        Some(sibling_path) if sibling_path.is_file() => Ok(Some(sibling_path)),
+        _ => Ok(None),
+    }
+}
+
+/// Tries to open the required file.
+/// Returns Ok(None) if the file does not exist or it is not a regular file.
+pub fn open_file(path: &path::Path) -> Result<Option<(fs::File, fs::Metadata)>, io::Error> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        // The file may have been deleted after the check
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    // Metadata of the opened file, so it can't be replaced between the check and the reading
+    let metadata = file.metadata()?;
+    // Only regular files can be served
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+    Ok(Some((file, metadata)))
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Requested path is not absolute")]
+    NotAbsolute,
+    #[error("Wrong encoding of requested path: {0}")]
+    WrongEncoding(#[from] string::FromUtf8Error),
+    #[error("Requested path contains NUL byte")]
+    NulByte,
+    #[error("Requested path contains encoded separator")]
+    EncodedSeparator,
+    #[error("Requested path is outside the root folder")]
+    OutsideRoot,
+    #[error("Failed to canonicalize requested path: {0}")]
+    CanonicalizationFailed(io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_regular_paths() {
+        assert_eq!(normalize("/").unwrap(), path::PathBuf::new());
+        assert_eq!(
+            normalize("/css/style.css").unwrap(),
+            path::PathBuf::from("css/style.css")
+        );
+        assert_eq!(
+            normalize("/a/./b//c/../d.html").unwrap(),
+            path::PathBuf::from("a/b/d.html")
+        );
+        assert_eq!(
+            normalize("/my%20file.txt").unwrap(),
+            path::PathBuf::from("my file.txt")
+        );
+        // Double encoding is decoded only once, so it's just a strange file name
+        assert_eq!(
+            normalize("/%252e%252e/etc").unwrap(),
+            path::PathBuf::from("%2e%2e/etc")
+        );
+        // Dots that are not the whole segment are a regular name
+        assert_eq!(
+            normalize("/..../file").unwrap(),
+            path::PathBuf::from("..../file")
+        );
+    }
+
+    #[test]
+    fn normalize_rejects_traversal() {
+        let traversals = [
+            "/../etc/passwd",
+            "/../../etc/passwd",
+            "/a/../../etc/passwd",
+            "/./../etc/passwd",
+            "/%2e%2e/etc/passwd",
+            "/%2E%2E/etc/passwd",
+            "/.%2e/etc/passwd",
+            "/a/%2e%2e/%2e%2e/etc/passwd",
+        ];
+        for traversal in traversals {
+            assert!(
+                matches!(normalize(traversal), Err(Error::OutsideRoot)),
+                "{traversal}"
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_rejects_encoded_separators() {
+        let paths = [
+            "/%2e%2e%2fetc/passwd",
+            "/..%2Fetc/passwd",
+            "/%2Fetc/passwd",
+            "/..%5cetc",
+            "/..\\etc",
+            "/a\\..\\..\\etc",
+        ];
+        for path in paths {
+            assert!(
+                matches!(normalize(path), Err(Error::EncodedSeparator)),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_rejects_wrong_paths() {
+        assert!(matches!(
+            normalize("/index.html%00.png"),
+            Err(Error::NulByte)
+        ));
+        // Overlong UTF-8 encoding of '.'
+        assert!(matches!(
+            normalize("/%c0%ae%c0%ae/etc/passwd"),
+            Err(Error::WrongEncoding(_))
+        ));
+        assert!(matches!(normalize("etc/passwd"), Err(Error::NotAbsolute)));
+        assert!(matches!(normalize("*"), Err(Error::NotAbsolute)));
+    }
+
+    #[test]
+    fn resolve_inside_root() {
+        let root = fs::canonicalize("./www").unwrap();
+        let resolved = resolve(&root, path::Path::new("index.html")).unwrap();
+        assert_eq!(resolved, Some(root.join("index.html")));
+        let resolved = resolve(&root, path::Path::new("does_not_exist.html")).unwrap();
+        assert_eq!(resolved, None);
+    }
+
+    #[test]
+    fn find_index_in_priority_order() {
+        let temp_dir = std::env::temp_dir().join("simple_web_server_site_path_index");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("docs/index.html")).unwrap();
+        fs::write(temp_dir.join("docs/index.htm"), "htm").unwrap();
+        fs::write(temp_dir.join("docs/default.html"), "default").unwrap();
+
+        let root = temp_dir.canonicalize().unwrap();
+        let index_file_names = [
+            "index.html".to_string(),
+            "index.htm".to_string(),
+            "default.html".to_string(),
+        ];
+        // "index.html" is a folder, so it is skipped
+        let index = find_index(&root, path::Path::new("docs"), &index_file_names);
+        let missing_index = find_index(&root, path::Path::new(""), &index_file_names);
+        let _ = fs::remove_dir_all(&temp_dir);
+        assert_eq!(index.unwrap(), Some(root.join("docs/index.htm")));
+        assert_eq!(missing_index.unwrap(), None);
+    }
+
+    #[test]
+    fn find_sibling_files() {
+        let temp_dir = std::env::temp_dir().join("simple_web_server_site_path_sibling");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("js/app.js.gz")).unwrap();
+        fs::write(temp_dir.join("js/app.js"), "app").unwrap();
+        fs::write(temp_dir.join("js/app.js.br"), "br").unwrap();
+
+        let root = temp_dir.canonicalize().unwrap();
+        let app_path = path::Path::new("js/app.js");
+        let brotli = find_sibling(&root, app_path, "br");
+        // "app.js.gz" is a folder
+        let gzip = find_sibling(&root, app_path, "gz");
+        let zstd = find_sibling(&root, app_path, "zst");
+        let _ = fs::remove_dir_all(&temp_dir);
+        assert_eq!(brotli.unwrap(), Some(root.join("js/app.js.br")));
+        assert_eq!(gzip.unwrap(), None);
+        assert_eq!(zstd.unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_rejects_symlink_outside_root() {
+        let temp_dir = std::env::temp_dir().join("simple_web_server_site_path_symlink");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let root = temp_dir.join("root");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(temp_dir.join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(temp_dir.join("secret.txt"), root.join("link.txt")).unwrap();
+
+        let root = root.canonicalize().unwrap();
+        let result = resolve(&root, path::Path::new("link.txt"));
+        let _ = fs::remove_dir_all(&temp_dir);
+        assert!(matches!(result, Err(Error::OutsideRoot)));
+    }
+
+    #[test]
+    fn open_missing_file() {
+        let path = path::Path::new("./www/does_not_exist.bin");
+        assert!(matches!(open_file(path), Ok(None)));
+        // Folders are not served as files
+        assert!(matches!(open_file(path::Path::new("./www")), Ok(None)));
+    }
+
+    #[test]
+    fn open_non_utf8_file() {
+        let path = std::env::temp_dir().join("simple_web_server_site_path_non_utf8.bin");
+        fs::write(&path, [0xC3, 0x28, 0xFF]).unwrap();
+        let file = open_file(&path);
+        let _ = fs::remove_file(&path);
+        let (mut file, metadata) = file.unwrap().unwrap();
+        assert_eq!(metadata.len(), 3);
+        let mut content = Vec::new();
+        io::Read::read_to_end(&mut file, &mut content).unwrap();
+        assert_eq!(content, [0xC3, 0x28, 0xFF]);
+    }
+}
@@ -0,0 +1,88 @@
+/// Connection streams of the HTTP connection: plain TCP, TLS over TCP and Unix domain socket
+use crate::{response, tls};
+use std::{io, net, time};
+
+#[cfg(unix)]
+use std::os::unix::net as unix_net;
+
+/// Stream the HTTP connection is served over.
+/// The requests are read from and the responses are written to the shared reference of the stream.
+pub trait ConnectionStream: Send + 'static
+where
+    for<'a> &'a Self: io::Read + io::Write,
+{
+    /// Performs the handshake of the protocol under HTTP, there is nothing to do for the plain sockets
+    fn handshake(&self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    fn set_read_timeout(&self, timeout: Option<time::Duration>) -> Result<(), io::Error>;
+
+    fn shutdown(&self) -> Result<(), io::Error>;
+
+    /// Writes the response to the stream.
+    /// The plain sockets can send the file bodies by sendfile(2), the other streams copy them through the userspace buffer.
+    fn write_response(
+        &self,
+        response: &response::Response,
+        include_body: bool,
+        use_sendfile: bool,
+    ) -> Result<(), io::Error> {
+        let _ = use_sendfile;
+        response.write_to(io::BufWriter::new(self), include_body)
+    }
+}
+
+impl ConnectionStream for net::TcpStream {
+    fn set_read_timeout(&self, timeout: Option<time::Duration>) -> Result<(), io::Error> {
+        net::TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn shutdown(&self) -> Result<(), io::Error> {
+        net::TcpStream::shutdown(self, net::Shutdown::Both)
+    }
+
+    fn write_response(
+        &self,
+        response: &response::Response,
+        include_body: bool,
+        use_sendfile: bool,
+    ) -> Result<(), io::Error> {
+        response.write_to_socket(self, include_body, use_sendfile)
+    }
+}
+
+/// The encrypted data can not be sent by sendfile(2), so the default write_response is used
+impl ConnectionStream for tls::TlsStream {
+    fn handshake(&self) -> Result<(), io::Error> {
+        tls::TlsStream::handshake(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<time::Duration>) -> Result<(), io::Error> {
+        tls::TlsStream::set_read_timeout(self, timeout)
+    }
+
+    fn shutdown(&self) -> Result<(), io::Error> {
+        tls::TlsStream::shutdown(self)
+    }
+}
+
+#[cfg(unix)]
+impl ConnectionStream for unix_net::UnixStream {
+    fn set_read_timeout(&self, timeout: Option<time::Duration>) -> Result<(), io::Error> {
+        unix_net::UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn shutdown(&self) -> Result<(), io::Error> {
+        unix_net::UnixStream::shutdown(self, net::Shutdown::Both)
+    }
+
+    fn write_response(
+        &self,
+        response: &response::Response,
+        include_body: bool,
+        use_sendfile: bool,
+    ) -> Result<(), io::Error> {
+        response.write_to_socket(self, include_body, use_sendfile)
+    }
+}
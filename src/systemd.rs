@@ -0,0 +1,257 @@
+/// systemd integration: socket activation (the sockets passed in LISTEN_FDS) and service state notifications (NOTIFY_SOCKET)
+use std::{
+    env, io, mem, net,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    os::linux::net::SocketAddrExt,
+    os::unix::{ffi::OsStrExt, net as unix_net},
+    process, time,
+};
+
+/// The first descriptor passed by systemd, 0-2 are the standard streams
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Listening socket passed by systemd
+pub enum ActivatedSocket {
+    Tcp(net::TcpListener),
+    Unix(unix_net::UnixListener),
+}
+
+/// Takes the listening sockets passed by systemd (sd_listen_fds protocol).
+/// The sockets are passed to this process only if LISTEN_PID is its PID.
+/// The variables are removed, so the sockets are not taken again by the child processes.
+pub fn listen_sockets() -> Result<Vec<ActivatedSocket>, io::Error> {
+    let listen_pid = env::var("LISTEN_PID").ok();
+    let listen_fds = env::var("LISTEN_FDS").ok();
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(name);
+    }
+    let fds_number = fds_number(listen_pid.as_deref(), listen_fds.as_deref(), process::id())?;
+    (LISTEN_FDS_START..LISTEN_FDS_START + fds_number)
+        .map(adopt_socket)
+        .collect()
+}
+
+/// Checks the LISTEN_PID and LISTEN_FDS values, returns the number of the passed descriptors
+fn fds_number(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    pid: u32,
+) -> Result<RawFd, io::Error> {
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no sockets are passed (LISTEN_PID and LISTEN_FDS are not set)",
+        ));
+    };
+    if listen_pid.trim().parse::<u32>().ok() != Some(pid) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("the sockets are passed to another process (LISTEN_PID={listen_pid})"),
+        ));
+    }
+    match listen_fds.trim().parse::<RawFd>() {
+        Ok(fds_number) if fds_number > 0 && fds_number <= RawFd::MAX - LISTEN_FDS_START => {
+            Ok(fds_number)
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("wrong number of the passed sockets (LISTEN_FDS={listen_fds})"),
+        )),
+    }
+}
+
+/// Takes the ownership of the passed descriptor, it must be a listening stream socket (TCP or Unix domain)
+fn adopt_socket(fd: RawFd) -> Result<ActivatedSocket, io::Error> {
+    // The descriptors are inherited without FD_CLOEXEC, the flag is set as it is for the sockets bound by the server.
+    // This also checks that the descriptor is open.
+    // SAFETY: fcntl does not access the memory of the process.
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the descriptor is open and systemd passes its ownership to the process, it is taken only once.
+    let socket = socket2::Socket::from(unsafe { OwnedFd::from_raw_fd(fd) });
+    let not_listening = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("descriptor {fd} is not a listening stream socket"),
+        )
+    };
+    if socket.r#type()? != socket2::Type::STREAM || !is_listening(&socket)? {
+        return Err(not_listening());
+    }
+    let local_addr = socket.local_addr()?;
+    if local_addr.as_socket().is_some() {
+        Ok(ActivatedSocket::Tcp(socket.into()))
+    } else if local_addr.is_unix() {
+        Ok(ActivatedSocket::Unix(OwnedFd::from(socket).into()))
+    } else {
+        Err(not_listening())
+    }
+}
+
+/// Checks that listen(2) has been called on the socket (SO_ACCEPTCONN option)
+fn is_listening(socket: &socket2::Socket) -> Result<bool, io::Error> {
+    let mut accepts_connections: libc::c_int = 0;
+    let mut length = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: the pointers point to the variables of the matching size that outlive the call.
+    let result = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_ACCEPTCONN,
+            (&mut accepts_connections as *mut libc::c_int).cast(),
+            &mut length,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(accepts_connections != 0)
+}
+
+/// Sender of the service state notifications to systemd (sd_notify protocol)
+pub struct Notifier {
+    socket: unix_net::UnixDatagram,
+    addr: unix_net::SocketAddr,
+    /// systemd restarts the service if WATCHDOG=1 is not sent within the watchdog timeout, None if the watchdog is off
+    watchdog_timeout: Option<time::Duration>,
+}
+
+impl Notifier {
+    /// Creates the notifier for the socket in NOTIFY_SOCKET, returns None if it is not set (the service is not Type=notify).
+    /// The socket path starting with '@' is the name in the abstract namespace.
+    pub fn from_env() -> Result<Option<Self>, io::Error> {
+        let Some(notify_socket) = env::var_os("NOTIFY_SOCKET") else {
+            return Ok(None);
+        };
+        let addr = match notify_socket.as_bytes().strip_prefix(b"@") {
+            Some(name) => unix_net::SocketAddr::from_abstract_name(name)?,
+            None => unix_net::SocketAddr::from_pathname(&notify_socket)?,
+        };
+        let watchdog_timeout = watchdog_timeout(
+            env::var("WATCHDOG_USEC").ok().as_deref(),
+            env::var("WATCHDOG_PID").ok().as_deref(),
+            process::id(),
+        );
+        Ok(Some(Self {
+            socket: unix_net::UnixDatagram::unbound()?,
+            addr,
+            watchdog_timeout,
+        }))
+    }
+
+    /// Sends the state, "READY=1" for example
+    pub fn notify(&self, state: &str) -> Result<(), io::Error> {
+        self.socket
+            .send_to_addr(state.as_bytes(), &self.addr)
+            .map(|_| ())
+    }
+
+    /// How often WATCHDOG=1 should be sent, it is half of the timeout as systemd recommends
+    pub fn watchdog_interval(&self) -> Option<time::Duration> {
+        self.watchdog_timeout.map(|timeout| timeout / 2)
+    }
+}
+
+/// Parses WATCHDOG_USEC, the watchdog is meant for this process if WATCHDOG_PID is its PID or is not set
+fn watchdog_timeout(
+    watchdog_usec: Option<&str>,
+    watchdog_pid: Option<&str>,
+    pid: u32,
+) -> Option<time::Duration> {
+    if watchdog_pid.is_some_and(|watchdog_pid| watchdog_pid.trim().parse::<u32>().ok() != Some(pid))
+    {
+        return None;
+    }
+    let watchdog_usec = watchdog_usec?.trim().parse::<u64>().ok()?;
+    (watchdog_usec > 0).then(|| time::Duration::from_micros(watchdog_usec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::fd::IntoRawFd;
+
+    #[test]
+    fn passed_fds_number() {
+        assert_eq!(fds_number(Some("100"), Some("2"), 100).unwrap(), 2);
+        assert_eq!(
+            fds_number(None, None, 100).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        for (listen_pid, listen_fds) in [("101", "2"), ("100", "0"), ("100", "-1"), ("100", "x")] {
+            assert_eq!(
+                fds_number(Some(listen_pid), Some(listen_fds), 100)
+                    .unwrap_err()
+                    .kind(),
+                io::ErrorKind::InvalidInput,
+                "{listen_pid} {listen_fds}"
+            );
+        }
+    }
+
+    #[test]
+    fn adopt_passed_sockets() {
+        let tcp_listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp_listener.local_addr().unwrap();
+        let fd = OwnedFd::from(tcp_listener).into_raw_fd();
+        let Ok(ActivatedSocket::Tcp(tcp_listener)) = adopt_socket(fd) else {
+            panic!("TCP listener is expected");
+        };
+        assert_eq!(tcp_listener.local_addr().unwrap(), addr);
+
+        let path = std::env::temp_dir().join("simple_web_server_systemd.sock");
+        let _ = std::fs::remove_file(&path);
+        let unix_listener = unix_net::UnixListener::bind(&path).unwrap();
+        let fd = OwnedFd::from(unix_listener).into_raw_fd();
+        let Ok(ActivatedSocket::Unix(unix_listener)) = adopt_socket(fd) else {
+            panic!("Unix listener is expected");
+        };
+        assert_eq!(
+            unix_listener.local_addr().unwrap().as_pathname(),
+            Some(path.as_path())
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        // Connected sockets are not accepted
+        let client = net::TcpStream::connect(tcp_listener.local_addr().unwrap()).unwrap();
+        let fd = OwnedFd::from(client).into_raw_fd();
+        assert!(adopt_socket(fd).is_err());
+    }
+
+    #[test]
+    fn watchdog_timeouts() {
+        assert_eq!(
+            watchdog_timeout(Some("3000000"), None, 100),
+            Some(time::Duration::from_secs(3))
+        );
+        assert_eq!(
+            watchdog_timeout(Some("3000000"), Some("100"), 100),
+            Some(time::Duration::from_secs(3))
+        );
+        assert_eq!(watchdog_timeout(Some("3000000"), Some("101"), 100), None);
+        assert_eq!(watchdog_timeout(Some("0"), None, 100), None);
+        assert_eq!(watchdog_timeout(None, None, 100), None);
+    }
+
+    #[test]
+    fn notifications() {
+        let path = std::env::temp_dir().join("simple_web_server_systemd_notify.sock");
+        let _ = std::fs::remove_file(&path);
+        let receiver = unix_net::UnixDatagram::bind(&path).unwrap();
+        let notifier = Notifier {
+            socket: unix_net::UnixDatagram::unbound().unwrap(),
+            addr: unix_net::SocketAddr::from_pathname(&path).unwrap(),
+            watchdog_timeout: Some(time::Duration::from_secs(10)),
+        };
+        assert_eq!(
+            notifier.watchdog_interval(),
+            Some(time::Duration::from_secs(5))
+        );
+        notifier.notify("READY=1").unwrap();
+        let mut buf = [0; 64];
+        let length = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..length], b"READY=1");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
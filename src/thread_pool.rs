@@ -10,7 +10,7 @@ pub struct ThreadPool {
     job_sender: Option<mpsc::Sender<Job>>,
 }
 
-type Job = Box<dyn FnOnce() -> () + Send + 'static>;
+type Job = Box<dyn FnOnce() + Send + 'static>;
 
 impl ThreadPool {
     /// Creates a ThreadPool and starts threads_number of threads ready for Jobs.
@@ -84,7 +84,7 @@ impl ThreadPool {
 
     /// Sends a Job to be executed in some thread.
     pub fn send_job(&self, job: Job) {
-        assert!(self.threads_handlers.len() > 0);
+        assert!(!self.threads_handlers.is_empty());
         // Send Job to the channel
         self.jobs_queue_size.fetch_add(1, atomic::Ordering::SeqCst);
         let result = self.job_sender.as_ref().unwrap().send(job);
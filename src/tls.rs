@@ -0,0 +1,670 @@
+/// HTTPS: TLS 1.2 and 1.3 by rustls, the certificate is selected by the server name (SNI)
+use crate::config;
+use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use std::{cell, fs, io, io::Write, net, path, sync, thread, time};
+
+/// How often the certificate files are checked for changes, they are reloaded without restarting the server
+const RELOAD_CHECK_INTERVAL: time::Duration = time::Duration::from_secs(10);
+
+/// Application protocols offered by ALPN, HTTP/2 is not supported
+const ALPN_PROTOCOLS: [&[u8]; 2] = [b"http/1.1", b"http/1.0"];
+
+/// TLS configurations of the listeners.
+/// The listeners with the same certificates share one configuration, so the certificate files are loaded
+/// and checked for changes by one reloading thread per certificate set.
+#[derive(Default)]
+pub struct ServerConfigs<'a> {
+    server_configs: Vec<(
+        &'a [config::TlsCertificate<'a>],
+        sync::Arc<rustls::ServerConfig>,
+    )>,
+}
+
+impl<'a> ServerConfigs<'a> {
+    /// Returns the configuration for the certificates, it is created for the first listener with them
+    pub fn get(
+        &mut self,
+        tls_certificates: &'a [config::TlsCertificate<'a>],
+    ) -> Result<sync::Arc<rustls::ServerConfig>, Error> {
+        let existing = self
+            .server_configs
+            .iter()
+            .find(|(certificates, _)| *certificates == tls_certificates);
+        if let Some((_, server_config)) = existing {
+            return Ok(sync::Arc::clone(server_config));
+        }
+        let server_config = server_config(tls_certificates)?;
+        self.server_configs
+            .push((tls_certificates, sync::Arc::clone(&server_config)));
+        Ok(server_config)
+    }
+}
+
+/// Creates the TLS configuration of the server from the certificates, with its own certificate reloading thread
+pub fn server_config(
+    tls_certificates: &[config::TlsCertificate],
+) -> Result<sync::Arc<rustls::ServerConfig>, Error> {
+    let provider = sync::Arc::new(rustls::crypto::ring::default_provider());
+    let resolver = sync::Arc::new(CertificateResolver::new(
+        tls_certificates,
+        sync::Arc::clone(&provider),
+    )?);
+    spawn_reloader(sync::Arc::downgrade(&resolver));
+    let mut server_config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13, &rustls::version::TLS12])
+        .map_err(Error::ConfigurationError)?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+    server_config.alpn_protocols = ALPN_PROTOCOLS
+        .iter()
+        .map(|protocol| protocol.to_vec())
+        .collect();
+    Ok(sync::Arc::new(server_config))
+}
+
+/// Checks the certificate files for changes every RELOAD_CHECK_INTERVAL in the background thread,
+/// so the handshakes never wait for the files. The thread stops when the resolver is dropped.
+fn spawn_reloader(resolver: sync::Weak<CertificateResolver>) {
+    thread::spawn(move || loop {
+        thread::sleep(RELOAD_CHECK_INTERVAL);
+        let Some(resolver) = resolver.upgrade() else {
+            return;
+        };
+        let (reloaded_number, errors) = resolver.reload();
+        if reloaded_number > 0 {
+            println!("TLS certificates reloaded: {reloaded_number}");
+        }
+        for error in errors {
+            eprintln!("TLS certificate reloading error, the previous certificate is kept: {error}");
+        }
+    });
+}
+
+/// Certificate chain with its private key, loaded from the files
+struct LoadedCertificate {
+    certified_key: sync::Arc<rustls::sign::CertifiedKey>,
+    /// Contents of the files, the certificate is reloaded when they change
+    cert_chain_pem: Vec<u8>,
+    private_key_pem: Vec<u8>,
+}
+
+/// Certificate for the server name
+struct Certificate {
+    /// None for the default certificate
+    host_name: Option<String>,
+    cert_chain_path: path::PathBuf,
+    private_key_path: path::PathBuf,
+    loaded: sync::RwLock<LoadedCertificate>,
+}
+
+/// Selects the certificate by the server name sent by the client (SNI).
+/// The certificates are reloaded by the background thread, the handshakes only read the loaded ones.
+pub struct CertificateResolver {
+    certificates: Vec<Certificate>,
+    provider: sync::Arc<rustls::crypto::CryptoProvider>,
+}
+
+impl CertificateResolver {
+    /// Loads the certificates, at least one is required
+    pub fn new(
+        tls_certificates: &[config::TlsCertificate],
+        provider: sync::Arc<rustls::crypto::CryptoProvider>,
+    ) -> Result<Self, Error> {
+        if tls_certificates.is_empty() {
+            return Err(Error::NoCertificates);
+        }
+        let certificates = tls_certificates
+            .iter()
+            .map(|tls_certificate| {
+                let loaded = load_certificate(
+                    tls_certificate.cert_chain_path,
+                    tls_certificate.private_key_path,
+                    &provider,
+                )?;
+                Ok(Certificate {
+                    host_name: tls_certificate.host_name.map(str::to_ascii_lowercase),
+                    cert_chain_path: tls_certificate.cert_chain_path.to_path_buf(),
+                    private_key_path: tls_certificate.private_key_path.to_path_buf(),
+                    loaded: sync::RwLock::new(loaded),
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Self {
+            certificates,
+            provider,
+        })
+    }
+
+    /// Reloads the certificates whose files have changed, each certificate separately.
+    /// Returns the number of reloaded certificates and the errors of the certificates whose new files are wrong,
+    /// the previous certificate is kept for them.
+    pub fn reload(&self) -> (usize, Vec<Error>) {
+        let mut reloaded_number = 0;
+        let mut errors = Vec::new();
+        for certificate in &self.certificates {
+            match self.reload_certificate(certificate) {
+                Ok(true) => reloaded_number += 1,
+                Ok(false) => {}
+                Err(error) => errors.push(error),
+            }
+        }
+        (reloaded_number, errors)
+    }
+
+    /// Reloads the certificate if its files have changed, returns true if it is reloaded
+    fn reload_certificate(&self, certificate: &Certificate) -> Result<bool, Error> {
+        let cert_chain_pem = read_file(&certificate.cert_chain_path)?;
+        let private_key_pem = read_file(&certificate.private_key_path)?;
+        {
+            let loaded = certificate.loaded.read().unwrap();
+            if loaded.cert_chain_pem == cert_chain_pem && loaded.private_key_pem == private_key_pem
+            {
+                return Ok(false);
+            }
+        }
+        let certified_key = certified_key(
+            &cert_chain_pem,
+            &certificate.cert_chain_path,
+            &private_key_pem,
+            &certificate.private_key_path,
+            &self.provider,
+        )?;
+        *certificate.loaded.write().unwrap() = LoadedCertificate {
+            certified_key,
+            cert_chain_pem,
+            private_key_pem,
+        };
+        Ok(true)
+    }
+
+    /// Finds the certificate for the server name.
+    /// The names are compared case-insensitively, "*.example.com" matches one label ("www.example.com").
+    /// Without the server name or if no certificate matches, the default certificate (or the first one) is used.
+    pub fn find(&self, server_name: Option<&str>) -> sync::Arc<rustls::sign::CertifiedKey> {
+        let server_name = server_name.map(str::to_ascii_lowercase);
+        let certificate = server_name
+            .and_then(|server_name| {
+                let host_name_matches = |certificate: &&Certificate| {
+                    certificate
+                        .host_name
+                        .as_deref()
+                        .is_some_and(|host_name| host_name_matches(host_name, &server_name))
+                };
+                self.certificates.iter().find(host_name_matches)
+            })
+            .or_else(|| {
+                self.certificates
+                    .iter()
+                    .find(|certificate| certificate.host_name.is_none())
+            })
+            .unwrap_or(&self.certificates[0]);
+        let loaded = certificate.loaded.read().unwrap();
+        sync::Arc::clone(&loaded.certified_key)
+    }
+}
+
+impl rustls::server::ResolvesServerCert for CertificateResolver {
+    fn resolve(
+        &self,
+        client_hello: rustls::server::ClientHello<'_>,
+    ) -> Option<sync::Arc<rustls::sign::CertifiedKey>> {
+        Some(self.find(client_hello.server_name()))
+    }
+}
+
+impl std::fmt::Debug for CertificateResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertificateResolver")
+            .field(
+                "host_names",
+                &self
+                    .certificates
+                    .iter()
+                    .map(|certificate| certificate.host_name.as_deref())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Checks if the server name matches the host name of the certificate, both are in lowercase
+fn host_name_matches(host_name: &str, server_name: &str) -> bool {
+    match host_name.strip_prefix("*.") {
+        Some(domain) => server_name
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest == domain),
+        None => host_name == server_name,
+    }
+}
+
+fn read_file(path: &path::Path) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(|error| Error::FileReadError(path.to_path_buf(), error))
+}
+
+fn load_certificate(
+    cert_chain_path: &path::Path,
+    private_key_path: &path::Path,
+    provider: &rustls::crypto::CryptoProvider,
+) -> Result<LoadedCertificate, Error> {
+    let cert_chain_pem = read_file(cert_chain_path)?;
+    let private_key_pem = read_file(private_key_path)?;
+    let certified_key = certified_key(
+        &cert_chain_pem,
+        cert_chain_path,
+        &private_key_pem,
+        private_key_path,
+        provider,
+    )?;
+    Ok(LoadedCertificate {
+        certified_key,
+        cert_chain_pem,
+        private_key_pem,
+    })
+}
+
+/// Parses the PEM files, the chain starts with the server certificate, the key must match it
+fn certified_key(
+    cert_chain_pem: &[u8],
+    cert_chain_path: &path::Path,
+    private_key_pem: &[u8],
+    private_key_path: &path::Path,
+    provider: &rustls::crypto::CryptoProvider,
+) -> Result<sync::Arc<rustls::sign::CertifiedKey>, Error> {
+    let cert_chain = CertificateDer::pem_slice_iter(cert_chain_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| Error::WrongPemFile(cert_chain_path.to_path_buf(), error))?;
+    if cert_chain.is_empty() {
+        return Err(Error::NoCertificatesInFile(cert_chain_path.to_path_buf()));
+    }
+    let private_key = PrivateKeyDer::from_pem_slice(private_key_pem)
+        .map_err(|error| Error::WrongPemFile(private_key_path.to_path_buf(), error))?;
+    let certified_key = rustls::sign::CertifiedKey::from_der(cert_chain, private_key, provider)
+        .map_err(|error| Error::WrongPrivateKey(private_key_path.to_path_buf(), error))?;
+    Ok(sync::Arc::new(certified_key))
+}
+
+/// TLS connection over the TCP stream.
+/// Like TcpStream, it is read and written through a shared reference,
+/// so the requests are read by BufReader while the responses are written to the same stream.
+pub struct TlsStream {
+    stream: cell::RefCell<rustls::StreamOwned<rustls::ServerConnection, net::TcpStream>>,
+}
+
+impl TlsStream {
+    pub fn new(
+        server_config: sync::Arc<rustls::ServerConfig>,
+        tcp_stream: net::TcpStream,
+    ) -> Result<Self, Error> {
+        let connection =
+            rustls::ServerConnection::new(server_config).map_err(Error::ConnectionError)?;
+        Ok(Self {
+            stream: cell::RefCell::new(rustls::StreamOwned::new(connection, tcp_stream)),
+        })
+    }
+
+    /// Performs the handshake, otherwise it is done by the first read or write
+    pub fn handshake(&self) -> Result<(), io::Error> {
+        let mut stream = self.stream.borrow_mut();
+        let rustls::StreamOwned { conn, sock } = &mut *stream;
+        while conn.is_handshaking() {
+            conn.complete_io(sock)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<time::Duration>) -> Result<(), io::Error> {
+        self.stream.borrow().sock.set_read_timeout(timeout)
+    }
+
+    /// Sends the close_notify alert and shuts down the TCP stream
+    pub fn shutdown(&self) -> Result<(), io::Error> {
+        let mut stream = self.stream.borrow_mut();
+        stream.conn.send_close_notify();
+        let result = stream
+            .flush()
+            .and_then(|_| stream.sock.shutdown(net::Shutdown::Both));
+        match result {
+            // The client has already closed the connection and reset it on receiving the alert
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::NotConnected
+                        | io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::BrokenPipe
+                ) =>
+            {
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+impl io::Read for &TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.stream.borrow_mut().read(buf) {
+            // Many clients close the connection without the close_notify alert,
+            // the request head is complete anyway, so it is treated as the normal end of the connection
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+            result => result,
+        }
+    }
+}
+
+impl io::Write for &TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.borrow_mut().flush()
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    #[error("No TLS certificates.")]
+    NoCertificates,
+    #[error("Failed to read {0}: {1}")]
+    FileReadError(path::PathBuf, io::Error),
+    #[error("Wrong PEM file {0}: {1}")]
+    WrongPemFile(path::PathBuf, rustls::pki_types::pem::Error),
+    #[error("No certificates in {0}.")]
+    NoCertificatesInFile(path::PathBuf),
+    #[error("Wrong private key {0}: {1}")]
+    WrongPrivateKey(path::PathBuf, rustls::Error),
+    #[error("TLS configuration error: {0}")]
+    ConfigurationError(rustls::Error),
+    #[error("TLS connection error: {0}")]
+    ConnectionError(rustls::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// Self-signed certificate and key written to the temporary PEM files
+    struct TestCertificate {
+        cert_chain_path: path::PathBuf,
+        private_key_path: path::PathBuf,
+        der: Vec<u8>,
+    }
+
+    impl TestCertificate {
+        fn generate(name: &str, host_names: &[&str]) -> Self {
+            let certified_key = rcgen::generate_simple_self_signed(
+                host_names
+                    .iter()
+                    .map(|host_name| host_name.to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+            let temp_dir = std::env::temp_dir();
+            let test_certificate = Self {
+                cert_chain_path: temp_dir.join(format!("simple_web_server_tls_{name}.crt")),
+                private_key_path: temp_dir.join(format!("simple_web_server_tls_{name}.key")),
+                der: certified_key.cert.der().to_vec(),
+            };
+            fs::write(&test_certificate.cert_chain_path, certified_key.cert.pem()).unwrap();
+            fs::write(
+                &test_certificate.private_key_path,
+                certified_key.signing_key.serialize_pem(),
+            )
+            .unwrap();
+            test_certificate
+        }
+
+        fn config(&self, host_name: Option<&'static str>) -> config::TlsCertificate<'_> {
+            config::TlsCertificate {
+                host_name,
+                cert_chain_path: &self.cert_chain_path,
+                private_key_path: &self.private_key_path,
+            }
+        }
+
+        fn remove(self) {
+            let _ = fs::remove_file(self.cert_chain_path);
+            let _ = fs::remove_file(self.private_key_path);
+        }
+    }
+
+    fn new_resolver(tls_certificates: &[config::TlsCertificate]) -> CertificateResolver {
+        let provider = sync::Arc::new(rustls::crypto::ring::default_provider());
+        CertificateResolver::new(tls_certificates, provider).unwrap()
+    }
+
+    fn found_der(resolver: &CertificateResolver, server_name: Option<&str>) -> Vec<u8> {
+        resolver.find(server_name).cert[0].to_vec()
+    }
+
+    /// Client accepting any certificate, the test checks which certificate is sent
+    #[derive(Debug)]
+    struct AnyCertificateVerifier(rustls::crypto::CryptoProvider);
+
+    impl rustls::client::danger::ServerCertVerifier for AnyCertificateVerifier {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &rustls::pki_types::ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: rustls::pki_types::UnixTime,
+        ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+            Ok(rustls::client::danger::ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &rustls::DigitallySignedStruct,
+        ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &rustls::DigitallySignedStruct,
+        ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    /// Connects to the server, sends the message and reads the answer.
+    /// Returns the server certificate, the negotiated protocol version and ALPN protocol, and the answer.
+    fn exchange(
+        server_config: sync::Arc<rustls::ServerConfig>,
+        server_name: &'static str,
+        version: &'static rustls::SupportedProtocolVersion,
+    ) -> (Vec<u8>, rustls::ProtocolVersion, Option<Vec<u8>>, String) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (tcp_stream, _) = listener.accept().unwrap();
+            let tls_stream = TlsStream::new(server_config, tcp_stream).unwrap();
+            tls_stream.handshake().unwrap();
+            let mut message = [0; 5];
+            (&tls_stream).read_exact(&mut message).unwrap();
+            (&tls_stream).write_all(b"pong").unwrap();
+            (&tls_stream).flush().unwrap();
+            tls_stream.shutdown().unwrap();
+            message
+        });
+
+        let provider = rustls::crypto::ring::default_provider();
+        let mut client_config =
+            rustls::ClientConfig::builder_with_provider(sync::Arc::new(provider.clone()))
+                .with_protocol_versions(&[version])
+                .unwrap()
+                .dangerous()
+                .with_custom_certificate_verifier(sync::Arc::new(AnyCertificateVerifier(provider)))
+                .with_no_client_auth();
+        client_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        let connection = rustls::ClientConnection::new(
+            sync::Arc::new(client_config),
+            server_name.try_into().unwrap(),
+        )
+        .unwrap();
+        let mut client =
+            rustls::StreamOwned::new(connection, net::TcpStream::connect(addr).unwrap());
+        client.write_all(b"ping!").unwrap();
+        let mut answer = String::new();
+        client.read_to_string(&mut answer).unwrap();
+        assert_eq!(&server.join().unwrap(), b"ping!");
+        (
+            client.conn.peer_certificates().unwrap()[0].to_vec(),
+            client.conn.protocol_version().unwrap(),
+            client.conn.alpn_protocol().map(<[u8]>::to_vec),
+            answer,
+        )
+    }
+
+    #[test]
+    fn handshake_with_sni_and_alpn() {
+        let default = TestCertificate::generate("default", &["localhost"]);
+        let example = TestCertificate::generate("example", &["example.com", "*.example.com"]);
+        let server_config =
+            server_config(&[default.config(None), example.config(Some("*.example.com"))]).unwrap();
+
+        let (der, version, alpn_protocol, answer) = exchange(
+            sync::Arc::clone(&server_config),
+            "www.example.com",
+            &rustls::version::TLS13,
+        );
+        assert_eq!(der, example.der);
+        assert_eq!(version, rustls::ProtocolVersion::TLSv1_3);
+        assert_eq!(alpn_protocol.as_deref(), Some(&b"http/1.1"[..]));
+        assert_eq!(answer, "pong");
+
+        let (der, version, _, answer) =
+            exchange(server_config, "localhost", &rustls::version::TLS12);
+        assert_eq!(der, default.der);
+        assert_eq!(version, rustls::ProtocolVersion::TLSv1_2);
+        assert_eq!(answer, "pong");
+
+        default.remove();
+        example.remove();
+    }
+
+    #[test]
+    fn shared_server_configs() {
+        let first = TestCertificate::generate("shared_first", &["localhost"]);
+        let second = TestCertificate::generate("shared_second", &["localhost"]);
+        let first_certificates = [first.config(None)];
+        let same_certificates = [first.config(None)];
+        let second_certificates = [second.config(None)];
+        let mut server_configs = ServerConfigs::default();
+        let first_config = server_configs.get(&first_certificates).unwrap();
+        let same_config = server_configs.get(&same_certificates).unwrap();
+        let second_config = server_configs.get(&second_certificates).unwrap();
+        assert!(sync::Arc::ptr_eq(&first_config, &same_config));
+        assert!(!sync::Arc::ptr_eq(&first_config, &second_config));
+        assert_eq!(server_configs.server_configs.len(), 2);
+        first.remove();
+        second.remove();
+    }
+
+    #[test]
+    fn certificate_selection() {
+        let first = TestCertificate::generate("first", &["first.test"]);
+        let second = TestCertificate::generate("second", &["second.test"]);
+        let default = TestCertificate::generate("selection_default", &["localhost"]);
+
+        let resolver = new_resolver(&[
+            first.config(Some("first.test")),
+            second.config(Some("*.second.test")),
+        ]);
+        assert_eq!(found_der(&resolver, Some("First.TEST")), first.der);
+        assert_eq!(found_der(&resolver, Some("www.second.test")), second.der);
+        // Wildcard matches exactly one label
+        assert_eq!(found_der(&resolver, Some("second.test")), first.der);
+        assert_eq!(found_der(&resolver, Some("a.b.second.test")), first.der);
+        // Without the default certificate the first one is used
+        assert_eq!(found_der(&resolver, None), first.der);
+
+        let resolver = new_resolver(&[first.config(Some("first.test")), default.config(None)]);
+        assert_eq!(found_der(&resolver, Some("other.test")), default.der);
+        assert_eq!(found_der(&resolver, None), default.der);
+
+        first.remove();
+        second.remove();
+        default.remove();
+    }
+
+    #[test]
+    fn certificate_reloading() {
+        let old = TestCertificate::generate("reloading", &["localhost"]);
+        let other = TestCertificate::generate("reloading_other", &["other.test"]);
+        let resolver = new_resolver(&[old.config(None), other.config(Some("other.test"))]);
+        let (reloaded_number, errors) = resolver.reload();
+        assert_eq!(reloaded_number, 0);
+        assert!(errors.is_empty());
+
+        // The files are replaced by a new certificate
+        let new = TestCertificate::generate("reloading", &["localhost"]);
+        let (reloaded_number, errors) = resolver.reload();
+        assert_eq!(reloaded_number, 1);
+        assert!(errors.is_empty());
+        assert_eq!(found_der(&resolver, None), new.der);
+
+        // A wrong key is not loaded, the previous certificate is kept
+        fs::write(&new.private_key_path, "wrong").unwrap();
+        // The broken certificate does not block the reloading of the other one
+        let other_new = TestCertificate::generate("reloading_other", &["other.test"]);
+        let (reloaded_number, errors) = resolver.reload();
+        assert_eq!(reloaded_number, 1);
+        assert!(matches!(
+            errors.as_slice(),
+            [Error::WrongPemFile(path, _)] if *path == new.private_key_path
+        ));
+        assert_eq!(found_der(&resolver, None), new.der);
+        assert_eq!(found_der(&resolver, Some("other.test")), other_new.der);
+
+        // The missing file is reported too
+        let new_der = new.der.clone();
+        new.remove();
+        let (reloaded_number, errors) = resolver.reload();
+        assert_eq!(reloaded_number, 0);
+        assert!(matches!(errors.as_slice(), [Error::FileReadError(_, _)]));
+        assert_eq!(found_der(&resolver, None), new_der);
+
+        other_new.remove();
+    }
+
+    #[test]
+    fn mismatched_private_key() {
+        let first = TestCertificate::generate("mismatched_first", &["localhost"]);
+        let second = TestCertificate::generate("mismatched_second", &["localhost"]);
+        let provider = sync::Arc::new(rustls::crypto::ring::default_provider());
+        let result = CertificateResolver::new(
+            &[config::TlsCertificate {
+                host_name: None,
+                cert_chain_path: &first.cert_chain_path,
+                private_key_path: &second.private_key_path,
+            }],
+            provider,
+        );
+        assert!(matches!(result, Err(Error::WrongPrivateKey(_, _))));
+
+        first.remove();
+        second.remove();
+    }
+}
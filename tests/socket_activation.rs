@@ -0,0 +1,124 @@
+//! The server started the way systemd starts it: with the passed listening socket and the notification socket
+#![cfg(target_os = "linux")]
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    net,
+    os::fd::AsRawFd,
+    os::unix::{net as unix_net, process::CommandExt},
+    path, process, time,
+};
+
+/// Receives the notifications until the expected state arrives
+fn wait_for_state(notify_socket: &unix_net::UnixDatagram, state: &str) {
+    let mut buf = [0; 256];
+    loop {
+        let length = notify_socket
+            .recv(&mut buf)
+            .unwrap_or_else(|error| panic!("{state} is not received: {error}"));
+        if &buf[..length] == state.as_bytes() {
+            return;
+        }
+    }
+}
+
+/// Command starting the server with the listener passed as descriptor 3.
+/// LISTEN_PID is the PID of the shell that is replaced by the server.
+fn activated_server_command(
+    tcp_listener: &net::TcpListener,
+    root_folder_path: &path::Path,
+) -> process::Command {
+    let listener_fd = tcp_listener.as_raw_fd();
+    let mut command = process::Command::new("/bin/sh");
+    command
+        .args(["-c", "LISTEN_PID=$$ exec \"$0\" \"$@\""])
+        .arg(env!("CARGO_BIN_EXE_simple_web_server"))
+        .arg("--socket-activation")
+        .arg("--root_folder")
+        .arg(root_folder_path)
+        .env("LISTEN_FDS", "1")
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null());
+    // SAFETY: only async-signal-safe functions are called between fork and exec.
+    unsafe {
+        command.pre_exec(move || {
+            // dup2 does not clear FD_CLOEXEC if the descriptor is already 3
+            let result = if listener_fd == 3 {
+                libc::fcntl(3, libc::F_SETFD, 0)
+            } else {
+                libc::dup2(listener_fd, 3)
+            };
+            if result == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    command
+}
+
+#[test]
+fn socket_activation_and_notifications() {
+    let temp_dir = std::env::temp_dir();
+    let root_folder_path = temp_dir.join("simple_web_server_socket_activation");
+    fs::create_dir_all(&root_folder_path).unwrap();
+    fs::write(root_folder_path.join("index.html"), "activated").unwrap();
+    let notify_path = temp_dir.join("simple_web_server_socket_activation_notify.sock");
+    let _ = fs::remove_file(&notify_path);
+    let notify_socket = unix_net::UnixDatagram::bind(&notify_path).unwrap();
+    notify_socket
+        .set_read_timeout(Some(time::Duration::from_secs(10)))
+        .unwrap();
+
+    let tcp_listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = tcp_listener.local_addr().unwrap();
+    let mut command = activated_server_command(&tcp_listener, &root_folder_path);
+    command
+        .env("NOTIFY_SOCKET", &notify_path)
+        .env("WATCHDOG_USEC", "200000");
+    let mut server = command.spawn().unwrap();
+
+    wait_for_state(&notify_socket, "READY=1");
+    let mut stream = net::TcpStream::connect(addr).unwrap();
+    stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with("\r\n\r\nactivated"), "{response}");
+
+    wait_for_state(&notify_socket, "WATCHDOG=1");
+    // SAFETY: kill does not access the memory of the process.
+    assert_eq!(
+        unsafe { libc::kill(server.id() as libc::pid_t, libc::SIGINT) },
+        0
+    );
+    wait_for_state(&notify_socket, "STOPPING=1");
+    assert!(server.wait().unwrap().success());
+    fs::remove_file(&notify_path).unwrap();
+}
+
+#[test]
+fn listener_without_passed_socket() {
+    let root_folder_path = std::env::temp_dir().join("simple_web_server_socket_activation_missing");
+    fs::create_dir_all(&root_folder_path).unwrap();
+    // The passed socket is bound to another address than the listener with its own settings,
+    // the server must not serve the listener's traffic with the common settings
+    let tcp_listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let other_listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let other_addr = other_listener.local_addr().unwrap();
+    let output = activated_server_command(&tcp_listener, &root_folder_path)
+        .arg("--listen")
+        .arg(format!("{other_addr};root={}", root_folder_path.display()))
+        .stderr(process::Stdio::piped())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!(
+            "no socket is passed for the listener {other_addr}"
+        )),
+        "{stderr}"
+    );
+}